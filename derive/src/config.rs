@@ -1,13 +1,16 @@
-use std::collections::{BTreeMap, HashSet};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::path::{Path, PathBuf};
-use std::{env, fs};
+use std::rc::Rc;
+use std::{env, fs, iter};
 
 #[cfg(feature = "serde")]
 use serde::Deserialize;
 
 use crate::CompileError;
 use parser::node::Whitespace;
-use parser::Syntax;
+use parser::{Parsed, Syntax};
+use rewriter::{Naming, SourceMap};
 
 #[derive(Debug)]
 pub(crate) struct Config<'a> {
@@ -16,6 +19,20 @@ pub(crate) struct Config<'a> {
     pub(crate) default_syntax: &'a str,
     pub(crate) escapers: Vec<(HashSet<String>, String)>,
     pub(crate) whitespace: WhitespaceHandling,
+    pub(crate) jsx: JsxConfig,
+    /// Sorted files of the directories searched for components, read once.
+    listings: RefCell<HashMap<PathBuf, Rc<[PathBuf]>>>,
+}
+
+/// Where components used as JSX tags are looked up.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct JsxConfig {
+    /// Searched in order, after the directory of the template using the component.
+    pub(crate) dirs: Vec<PathBuf>,
+    /// Extensions of component files, without the leading dot, tried in order.
+    pub(crate) extensions: Vec<String>,
+    /// How component names map to file names.
+    pub(crate) naming: Naming,
 }
 
 impl<'a> Config<'a> {
@@ -79,6 +96,40 @@ impl<'a> Config<'a> {
             return Err(format!("default syntax \"{default_syntax}\" not found").into());
         }
 
+        let jsx = match raw.jsx {
+            Some(RawJsx {
+                dirs: jsx_dirs,
+                extensions,
+                naming,
+            }) => JsxConfig {
+                dirs: jsx_dirs.map_or_else(
+                    || dirs.clone(),
+                    |v| v.into_iter().map(|dir| root.join(dir)).collect(),
+                ),
+                extensions: match extensions {
+                    Some(v) if v.is_empty() => {
+                        return Err("`extensions` of [jsx] may not be empty".into());
+                    }
+                    Some(v) => v
+                        .into_iter()
+                        .map(|ext| ext.trim_start_matches('.').to_string())
+                        .collect(),
+                    None => vec![DEFAULT_COMPONENT_EXTENSION.to_string()],
+                },
+                naming: match naming {
+                    Some("snake_case") | None => Naming::SnakeCase,
+                    Some("kebab-case") => Naming::KebabCase,
+                    Some("PascalCase") => Naming::PascalCase,
+                    Some(s) => return Err(format!("invalid value for `naming`: \"{s}\"").into()),
+                },
+            },
+            None => JsxConfig {
+                dirs: dirs.clone(),
+                extensions: vec![DEFAULT_COMPONENT_EXTENSION.to_string()],
+                naming: Naming::default(),
+            },
+        };
+
         let mut escapers = Vec::new();
         if let Some(configured) = raw.escaper {
             for escaper in configured {
@@ -102,6 +153,8 @@ impl<'a> Config<'a> {
             default_syntax,
             escapers,
             whitespace,
+            jsx,
+            listings: RefCell::default(),
         })
     }
 
@@ -130,6 +183,88 @@ impl<'a> Config<'a> {
         )
         .into())
     }
+
+    /// Finds the file of a component from its path without extension, like `forms/text_input`,
+    /// next to the template at `start_at` first, then in the `[jsx]` directories. Files with
+    /// the extension `ext` of the template using the component come before the `[jsx]` ones.
+    /// Fails if another component file of the same extension, in any of these directories,
+    /// defines the same macro under another file name.
+    pub(crate) fn find_component(
+        &self,
+        path: &str,
+        start_at: Option<&Path>,
+        ext: &str,
+    ) -> std::result::Result<Option<PathBuf>, CompileError> {
+        let macro_name = rewriter::normalize(path);
+        let extensions = iter::once(ext)
+            .chain(
+                self.jsx
+                    .extensions
+                    .iter()
+                    .map(String::as_str)
+                    .filter(|e| *e != ext),
+            )
+            .collect::<Vec<_>>();
+        // Where the file would be, the `forms` subdirectories for `forms/text_input`.
+        let dirs = start_at
+            .and_then(|start_at| start_at.parent())
+            .into_iter()
+            .chain(self.jsx.dirs.iter().map(|dir| dir.as_path()))
+            .map(|dir| dir.join(path).parent().unwrap().to_owned())
+            .collect::<Vec<_>>();
+        let file_name = Path::new(path).file_name().unwrap().to_str().unwrap();
+
+        let Some(found) = dirs.iter().find_map(|dir| {
+            let listing = self.list_dir(dir);
+            extensions.iter().find_map(|ext| {
+                let candidate = dir.join(format!("{file_name}.{ext}"));
+                listing.contains(&candidate).then_some(candidate)
+            })
+        }) else {
+            return Ok(None);
+        };
+
+        // A file of the same name in a later directory is shadowed, not a collision.
+        let mut siblings = vec![found.clone()];
+        for dir in &dirs {
+            for sibling in self.list_dir(dir).iter() {
+                if sibling.extension() == found.extension()
+                    && rewriter::normalize(sibling) == macro_name
+                    && !siblings
+                        .iter()
+                        .any(|other| other.file_name() == sibling.file_name())
+                {
+                    siblings.push(sibling.clone());
+                }
+            }
+        }
+
+        if let [first, second, ..] = &siblings[..] {
+            return Err(format!(
+                "component files {:?} and {:?} both define the macro `{macro_name}`",
+                first.to_str().unwrap(),
+                second.to_str().unwrap(),
+            )
+            .into());
+        }
+
+        Ok(Some(found))
+    }
+
+    /// Lists the files of `dir`, sorted, reading it only the first time.
+    fn list_dir(&self, dir: &Path) -> Rc<[PathBuf]> {
+        let mut listings = self.listings.borrow_mut();
+        let listing = listings.entry(dir.to_owned()).or_insert_with(|| {
+            let mut files = fs::read_dir(dir)
+                .into_iter()
+                .flatten()
+                .filter_map(|entry| Some(entry.ok()?.path()))
+                .collect::<Vec<_>>();
+            files.sort();
+            files.into()
+        });
+        Rc::clone(listing)
+    }
 }
 
 impl<'a> TryInto<Syntax<'a>> for RawSyntax<'a> {
@@ -187,6 +322,7 @@ struct RawConfig<'a> {
     general: Option<General<'a>>,
     syntax: Option<Vec<RawSyntax<'a>>>,
     escaper: Option<Vec<RawEscaper<'a>>>,
+    jsx: Option<RawJsx<'a>>,
 }
 
 impl RawConfig<'_> {
@@ -236,6 +372,14 @@ struct General<'a> {
     whitespace: WhitespaceHandling,
 }
 
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+struct RawJsx<'a> {
+    #[cfg_attr(feature = "serde", serde(borrow))]
+    dirs: Option<Vec<&'a str>>,
+    extensions: Option<Vec<&'a str>>,
+    naming: Option<&'a str>,
+}
+
 #[cfg_attr(feature = "serde", derive(Deserialize))]
 struct RawSyntax<'a> {
     name: &'a str,
@@ -291,13 +435,94 @@ pub(crate) fn get_template_source(tpl_path: &Path) -> std::result::Result<String
             if source.ends_with('\n') {
                 let _ = source.pop();
             }
-            Ok(rewriter::rewrite_source(tpl_path, source))
+            Ok(source)
         }
     }
 }
 
+/// Reads the JSX template at `tpl_path`, rewrites it into askama macros and parses them.
+/// Errors are reported at their position in the JSX source.
+pub(crate) fn parse_template(
+    tpl_path: &Path,
+    config: &Config<'_>,
+    syntax: &Syntax<'_>,
+) -> std::result::Result<Parsed, CompileError> {
+    let source = get_template_source(tpl_path)?;
+    let (rewritten, source_map) = rewrite_source(tpl_path, &source, false, false, config, syntax)?;
+    parse_rewritten(tpl_path, &source, false, rewritten, &source_map, syntax)
+}
+
+/// Reads the JSX template at `tpl_path`, pulled in by `{% include %}`, and parses it
+/// rewritten in place, with the imports of its components.
+pub(crate) fn parse_include(
+    tpl_path: &Path,
+    config: &Config<'_>,
+    syntax: &Syntax<'_>,
+) -> std::result::Result<Parsed, CompileError> {
+    let source = get_template_source(tpl_path)?;
+    let (rewritten, source_map) = rewrite_source(tpl_path, &source, false, true, config, syntax)?;
+    parse_rewritten(tpl_path, &source, false, rewritten, &source_map, syntax)
+}
+
+/// Rewrites the JSX `source` of the template at `tpl_path` into askama macros, along with
+/// a map of the rewritten source back to `source`. An `inline` source has no file to find
+/// components next to, an `include` is not wrapped in a macro.
+pub(crate) fn rewrite_source(
+    tpl_path: &Path,
+    source: &str,
+    inline: bool,
+    include: bool,
+    config: &Config<'_>,
+    syntax: &Syntax<'_>,
+) -> std::result::Result<(String, SourceMap), CompileError> {
+    let start_at = (!inline).then_some(tpl_path);
+    // Components are looked up with the extension of the template first, `html` for
+    // `page.html.j2`.
+    let extension = crate::input::extension(tpl_path).unwrap_or(DEFAULT_COMPONENT_EXTENSION);
+    let resolve = |path: &str| match config.find_component(path, start_at, extension) {
+        Ok(found) => Ok(found.map(|path| path.to_str().unwrap().to_owned())),
+        Err(err) => Err(err.to_string()),
+    };
+    let options = rewriter::Options {
+        syntax,
+        naming: config.jsx.naming,
+        resolve: &resolve,
+        include,
+        extension,
+    };
+    rewriter::rewrite_source(tpl_path, source, &options).map_err(|err| {
+        let name = template_name(tpl_path, inline);
+        format!("unable to rewrite {name}: {err}").into()
+    })
+}
+
+/// Parses the `rewritten` JSX `source` of the template at `tpl_path`, reporting errors at
+/// their position in `source`.
+pub(crate) fn parse_rewritten(
+    tpl_path: &Path,
+    source: &str,
+    inline: bool,
+    rewritten: String,
+    source_map: &SourceMap,
+    syntax: &Syntax<'_>,
+) -> std::result::Result<Parsed, CompileError> {
+    Parsed::new(rewritten, syntax).map_err(|err| {
+        let name = template_name(tpl_path, inline);
+        let offset = source_map.original_offset(err.offset());
+        format!("unable to parse {name}: {}", err.relocate(source, offset)).into()
+    })
+}
+
+fn template_name(tpl_path: &Path, inline: bool) -> String {
+    match inline {
+        true => "template source".to_owned(),
+        false => format!("template file '{}'", tpl_path.to_str().unwrap()),
+    }
+}
+
 static CONFIG_FILE_NAME: &str = "askama.toml";
 static DEFAULT_SYNTAX_NAME: &str = "default";
+static DEFAULT_COMPONENT_EXTENSION: &str = "html";
 static DEFAULT_ESCAPERS: &[(&[&str], &str)] = &[
     (&["html", "htm", "svg", "xml"], "::askama::Html"),
     (&["md", "none", "txt", "yml", ""], "::askama::Text"),
@@ -316,8 +541,47 @@ mod tests {
         let path = Config::new("", None)
             .and_then(|config| config.find_template("b.html", None))
             .unwrap();
-        #[rustfmt::skip]
-        assert_eq!(get_template_source(&path).unwrap(), "{% macro b() %}\nbar{% endmacro b %}\n");
+        assert_eq!(get_template_source(&path).unwrap(), "bar");
+    }
+
+    #[test]
+    fn parse_template_error() {
+        let config = Config::new("", None).unwrap();
+
+        let path = config.find_template("unclosed.html", None).unwrap();
+        assert_eq!(
+            parse_template(&path, &config, &Syntax::default())
+                .unwrap_err()
+                .to_string(),
+            format!(
+                "unable to rewrite template file '{}': unclosed tag <Card> at row 1, column 0",
+                path.to_str().unwrap()
+            )
+        );
+
+        let path = config.find_template("invalid.html", None).unwrap();
+        assert_eq!(
+            parse_template(&path, &config, &Syntax::default())
+                .unwrap_err()
+                .to_string(),
+            format!(
+                "unable to parse template file '{}': problems parsing template source \
+                 at row 2, column 11 near:\n\"%}}\"",
+                path.to_str().unwrap()
+            )
+        );
+    }
+
+    #[test]
+    fn rewrite_source_extension() {
+        let config = Config::new("", None).unwrap();
+        let path = config.dirs[0].join("page.txt.j2");
+        let (rewritten, _) =
+            rewrite_source(&path, "<B />", false, false, &config, &Syntax::default()).unwrap();
+        assert!(rewritten.starts_with(&format!(
+            "{{%- import {:?} as b_scope -%}}\n",
+            config.dirs[0].join("b.txt").to_str().unwrap()
+        )));
     }
 
     #[test]
@@ -337,6 +601,85 @@ mod tests {
         assert_eq!(config.dirs, vec![root]);
     }
 
+    #[cfg(feature = "config")]
+    #[test]
+    fn test_config_jsx() {
+        let root = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+        let config = Config::new(
+            "[jsx]\ndirs = [\"templates/sub\", \"templates\"]\nextensions = [\".jinja\", \"html\"]",
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            config.jsx,
+            JsxConfig {
+                dirs: vec![root.join("templates/sub"), root.join("templates")],
+                extensions: vec!["jinja".into(), "html".into()],
+                naming: Naming::SnakeCase,
+            }
+        );
+
+        let start_at = root.join("templates/a.html");
+        let find = |path| {
+            config
+                .find_component(path, Some(&start_at), "html")
+                .unwrap()
+                .unwrap()
+        };
+        assert_eq_rooted(&find("b"), "b.html");
+        assert_eq_rooted(&find("c"), "sub/c.html");
+        assert_eq_rooted(
+            &config
+                .find_component("b", Some(&start_at), "txt")
+                .unwrap()
+                .unwrap(),
+            "b.txt",
+        );
+        assert!(config
+            .find_component("missing", Some(&start_at), "html")
+            .unwrap()
+            .is_none());
+        assert_eq!(
+            config
+                .find_component("x_y", Some(&start_at), "html")
+                .unwrap_err()
+                .to_string(),
+            format!(
+                "component files {:?} and {:?} both define the macro `x_y`",
+                root.join("templates/x_y.html").to_str().unwrap(),
+                root.join("templates/sub/x-y.html").to_str().unwrap(),
+            )
+        );
+
+        let config = Config::new("", None).unwrap();
+        assert_eq!(config.jsx.dirs, config.dirs);
+        assert_eq!(config.jsx.extensions, vec!["html"]);
+        assert_eq!(config.jsx.naming, Naming::SnakeCase);
+
+        let config = Config::new("[jsx]\nnaming = \"kebab-case\"", None).unwrap();
+        assert_eq!(config.jsx.naming, Naming::KebabCase);
+        assert!(Config::new("[jsx]\nnaming = \"camelCase\"", None).is_err());
+
+        let collide = root.join("templates/collide");
+        assert_eq!(
+            config
+                .find_component(
+                    "hello-world",
+                    Some(&start_at.with_file_name("collide/a.html")),
+                    "html",
+                )
+                .unwrap_err()
+                .to_string(),
+            format!(
+                "component files {:?} and {:?} both define the macro `hello_world`",
+                collide.join("hello-world.html").to_str().unwrap(),
+                collide.join("hello_world.html").to_str().unwrap(),
+            )
+        );
+
+        assert!(Config::new("[jsx]\nextensions = []", None).is_err());
+    }
+
     fn assert_eq_rooted(actual: &Path, expected: &str) {
         let mut root = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
         root.push("templates");
@@ -514,7 +857,7 @@ mod tests {
         );
     }
 
-    #[cfg(feature = "toml")]
+    #[cfg(feature = "config")]
     #[should_panic]
     #[test]
     fn use_default_at_syntax_name() {
@@ -525,7 +868,7 @@ mod tests {
         let _config = Config::new(raw_config, None).unwrap();
     }
 
-    #[cfg(feature = "toml")]
+    #[cfg(feature = "config")]
     #[should_panic]
     #[test]
     fn duplicated_syntax_name_on_list() {
@@ -537,7 +880,7 @@ mod tests {
         let _config = Config::new(raw_config, None).unwrap();
     }
 
-    #[cfg(feature = "toml")]
+    #[cfg(feature = "config")]
     #[should_panic]
     #[test]
     fn is_not_exist_default_syntax() {
@@ -615,7 +958,7 @@ mod tests {
         assert_eq!(config.whitespace, WhitespaceHandling::Minimize);
     }
 
-    #[cfg(feature = "toml")]
+    #[cfg(feature = "config")]
     #[test]
     fn test_whitespace_in_template() {
         // Checking that template arguments have precedence over general configuration.
@@ -626,12 +969,12 @@ mod tests {
             [general]
             whitespace = "suppress"
             "#,
-            Some(&"minimize".to_owned()),
+            Some("minimize"),
         )
         .unwrap();
         assert_eq!(config.whitespace, WhitespaceHandling::Minimize);
 
-        let config = Config::new(r#""#, Some(&"minimize".to_owned())).unwrap();
+        let config = Config::new(r#""#, Some("minimize")).unwrap();
         assert_eq!(config.whitespace, WhitespaceHandling::Minimize);
     }
 
@@ -1,14 +1,16 @@
 use std::collections::hash_map::{Entry, HashMap};
+use std::collections::HashSet;
 use std::path::{Path, PathBuf};
 use std::{cmp, hash, mem, str};
 
-use crate::config::{get_template_source, WhitespaceHandling};
+use crate::config::{parse_include, WhitespaceHandling};
 use crate::heritage::{Context, Heritage};
-use crate::input::{Source, TemplateInput};
+use crate::input::TemplateInput;
 use crate::CompileError;
 
 use parser::node::{
-    Call, Comment, CondTest, If, Include, Let, Lit, Loop, Match, Target, Whitespace, Ws,
+    Call, Comment, CondTest, If, Include, Let, Lit, Loop, MacroArg, Match, Slot, Target,
+    Whitespace, Ws,
 };
 use parser::{Expr, Node, Parsed};
 use quote::quote;
@@ -33,8 +35,12 @@ pub(crate) struct Generator<'a> {
     skip_ws: WhitespaceHandling,
     // If currently in a block, this will contain the name of a potential parent block
     super_block: Option<(&'a str, usize)>,
-    // If currently in a macro call, this will contain the caller node
-    caller_node: Option<&'a Call<'a>>,
+    // The calls of the macros being expanded, innermost last, with the context of each
+    callers: Vec<(&'a Call<'a>, &'a Context<'a>)>,
+    // Recursive macros, which are compiled to functions instead of being expanded inline
+    functions: Vec<(PathBuf, String)>,
+    // If generating the function of a recursive macro, this will contain its name
+    function: Option<&'a str>,
     // buffer for writable
     buf_writable: Vec<Writable<'a>>,
     // Counter for write! hash named arguments
@@ -57,7 +63,9 @@ impl<'a> Generator<'a> {
             next_ws: None,
             skip_ws: WhitespaceHandling::Preserve,
             super_block: None,
-            caller_node: None,
+            callers: Vec::new(),
+            functions: vec![],
+            function: None,
             buf_writable: vec![],
             named: 0,
         }
@@ -68,6 +76,7 @@ impl<'a> Generator<'a> {
         let mut buf = Buffer::new(0);
 
         self.impl_template(ctx, &mut buf)?;
+        self.impl_functions(&mut buf)?;
         self.impl_display(&mut buf)?;
 
         #[cfg(feature = "with-actix-web")]
@@ -104,12 +113,9 @@ impl<'a> Generator<'a> {
 
         // Make sure the compiler understands that the generated code depends on the template files.
         for path in self.contexts.keys() {
-            // Skip the fake path of templates defined in rust source.
-            let path_is_valid = match self.input.source {
-                Source::Path(_) => true,
-                Source::Source(_) => path != &self.input.path,
-            };
-            if path_is_valid {
+            // Skip the fake path of templates defined in rust source, or generated to call
+            // the template file.
+            if path != &self.input.path {
                 let path = path.to_str().unwrap();
                 buf.writeln(
                     &quote! {
@@ -146,6 +152,87 @@ impl<'a> Generator<'a> {
         Ok(())
     }
 
+    // Implement the functions of the recursive macros called by the template.
+    fn impl_functions(&mut self, buf: &mut Buffer) -> Result<(), CompileError> {
+        if self.functions.is_empty() {
+            return Ok(());
+        }
+
+        let (impl_generics, orig_ty_generics, where_clause) =
+            self.input.ast.generics.split_for_impl();
+        buf.writeln(&format!(
+            "{} {}{} {{",
+            quote!(impl #impl_generics),
+            self.input.ast.ident,
+            quote!(#orig_ty_generics #where_clause),
+        ))?;
+
+        // Functions may call recursive macros that were not called before.
+        let mut index = 0;
+        while let Some((path, name)) = self.functions.get(index).cloned() {
+            self.write_function(buf, index, &path, &name)?;
+            index += 1;
+        }
+
+        buf.writeln("}")?;
+        Ok(())
+    }
+
+    fn write_function(
+        &mut self,
+        buf: &mut Buffer,
+        index: usize,
+        path: &Path,
+        name: &str,
+    ) -> Result<(), CompileError> {
+        let ctx = &self.contexts[path];
+        let (&name, &def) = ctx.macros.get_key_value(name).unwrap();
+
+        let mut params = String::new();
+        let mut locals = MapChain::default();
+        for arg in &def.args {
+            // Recursive macros are only called once all parameters have a type.
+            params.push_str(&format!(", {}: {}", arg.name, arg.ty.unwrap()));
+            locals.insert(arg.name, LocalMeta::initialized());
+        }
+
+        buf.writeln("#[allow(clippy::too_many_arguments)]")?;
+        buf.writeln(&format!(
+            "fn {}<JrsxWriter: ::std::fmt::Write + ?::std::marker::Sized>\
+             (&self, writer: &mut JrsxWriter{params}) -> ::askama::Result<()> {{",
+            function_name(index, name),
+        ))?;
+
+        let mut child = Self::new(self.input, self.contexts, self.heritage, locals);
+        child.functions = mem::take(&mut self.functions);
+        child.function = Some(name);
+        child.prepare_ws(def.ws1);
+        child.handle(ctx, &def.nodes, buf, AstLevel::Nested)?;
+        child.flush_ws(def.ws2);
+        child.write_buf_writable(buf)?;
+        self.functions = child.functions;
+
+        buf.writeln("::askama::Result::Ok(())")?;
+        buf.writeln("}")?;
+        Ok(())
+    }
+
+    // Returns the index of the function of a recursive macro, which is generated once the
+    // template is done.
+    fn function_index(&mut self, (path, name): MacroKey<'_>) -> usize {
+        match self
+            .functions
+            .iter()
+            .position(|key| key.0 == path && key.1 == name)
+        {
+            Some(index) => index,
+            None => {
+                self.functions.push((path.to_owned(), name.to_owned()));
+                self.functions.len() - 1
+            }
+        }
+    }
+
     // Implement `Display` for the given context struct.
     fn impl_display(&mut self, buf: &mut Buffer) -> Result<(), CompileError> {
         self.write_header(buf, "::std::fmt::Display", None)?;
@@ -433,6 +520,11 @@ impl<'a> Generator<'a> {
                     self.flush_ws(m.ws1);
                     self.prepare_ws(m.ws2);
                 }
+                Node::Slot(ref slot) => {
+                    // Slots are only rendered by name from within the called macro.
+                    self.flush_ws(slot.ws1);
+                    self.prepare_ws(slot.ws2);
+                }
                 Node::Raw(ref raw) => {
                     self.handle_ws(raw.ws1);
                     self.visit_lit(&raw.lit);
@@ -691,7 +783,10 @@ impl<'a> Generator<'a> {
             return self.write_block(buf, None, ws);
         }
         if name == "caller" {
-            return self.write_caller(ctx, buf, ws);
+            return self.write_caller(buf, ws, None);
+        }
+        if scope == Some("caller") {
+            return self.write_caller(buf, ws, Some(name));
         }
 
         let (def, own_ctx) = match scope {
@@ -704,6 +799,16 @@ impl<'a> Generator<'a> {
                     .get(path.as_path())
                     .ok_or_else(|| CompileError::from(format!("context for {path:?} not found")))?;
                 let def = mctx.macros.get(name).ok_or_else(|| {
+                    // Templates using inheritance are not wrapped in a component macro.
+                    if (mctx.extends.is_some() || !mctx.blocks.is_empty())
+                        && name == rewriter::normalize(path)
+                    {
+                        return CompileError::from(format!(
+                            "template {:?} uses `extends` or `block` tags and cannot be used as \
+                             a component",
+                            path.file_name().unwrap(),
+                        ));
+                    }
                     CompileError::from(format!("macro {name:?} not found in scope {s:?}"))
                 })?;
                 (def, mctx)
@@ -716,7 +821,51 @@ impl<'a> Generator<'a> {
                 (def, ctx)
             }
         };
-        self.caller_node = Some(call);
+
+        // A macro that calls itself, directly or through other macros, cannot be expanded
+        // inline: it is compiled to a function instead.
+        let (&own_path, _) = self.contexts.get_key_value(own_ctx.path).unwrap();
+        let function = match find_recursion(self.contexts, (own_path, name)) {
+            Some(cycle) => {
+                let cycle = cycle
+                    .iter()
+                    .map(|(path, name)| {
+                        format!("`{}::{name}`", path.file_name().unwrap().to_str().unwrap())
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" -> ");
+                if let Some(arg) = def.args.iter().find(|arg| arg.ty.is_none()) {
+                    return Err(format!(
+                        "recursive macro {name:?} must declare the type of its parameter `{}`, \
+                         in call cycle {cycle}",
+                        arg.name
+                    )
+                    .into());
+                }
+                if call
+                    .nodes
+                    .iter()
+                    .any(|node| !matches!(node, Node::Lit(lit) if lit.val.is_empty()))
+                {
+                    return Err(format!(
+                        "recursive macro {name:?} cannot be called with a body, \
+                         in call cycle {cycle}"
+                    )
+                    .into());
+                }
+                Some(self.function_index((own_path, name)))
+            }
+            None => None,
+        };
+
+        if let Some(slot) = find_nested_slot(call) {
+            return Err(format!(
+                "slot {:?} must be at the top level of the call of macro {name:?}",
+                slot.name
+            )
+            .into());
+        }
+        self.callers.push((call, ctx));
 
         self.flush_ws(ws); // Cannot handle_ws() here: whitespace from macro definition comes first
         self.locals.push();
@@ -727,9 +876,20 @@ impl<'a> Generator<'a> {
         let mut names = Buffer::new(0);
         let mut values = Buffer::new(0);
         let mut is_first_variable = true;
-        if args.len() != def.args.len() {
+
+        // A spread argument fills every parameter that is not passed explicitly.
+        let (spread, args) = match args.split_first() {
+            Some((Expr::Spread(spread), args)) => (Some(spread.as_ref()), args),
+            _ => (None, args.as_slice()),
+        };
+        if args.iter().any(|arg| matches!(arg, Expr::Spread(_))) {
             return Err(CompileError::from(format!(
-                "macro {name:?} expected {} argument{}, found {}",
+                "spread argument must be passed first in macro {name:?}"
+            )));
+        }
+        if args.len() > def.args.len() {
+            return Err(CompileError::from(format!(
+                "macro {name:?} expected at most {} argument{}, found {}",
                 def.args.len(),
                 if def.args.len() != 1 { "s" } else { "" },
                 args.len()
@@ -741,17 +901,18 @@ impl<'a> Generator<'a> {
         if let Some(Expr::NamedArgument(_, _)) = args.last() {
             // First we check that all named arguments actually exist in the called item.
             for arg in args.iter().rev() {
-                let Expr::NamedArgument(arg_name, _) = arg else {
+                let Expr::NamedArgument(arg_name, value) = arg else {
                     break;
                 };
-                if !def.args.iter().any(|arg| arg == arg_name) {
+                if !def.args.iter().any(|arg| arg.name == *arg_name) {
                     return Err(CompileError::from(format!(
                         "no argument named `{arg_name}` in macro {name:?}"
                     )));
                 }
-                named_arguments.insert(arg_name, arg);
+                named_arguments.insert(*arg_name, value.as_ref());
             }
         }
+        let positional = args.len() - named_arguments.len();
 
         // Handling both named and unnamed arguments requires to be careful of the named arguments
         // order. To do so, we iterate through the macro defined arguments and then check if we have
@@ -760,44 +921,70 @@ impl<'a> Generator<'a> {
         // * If there is one, we add it and move to the next argument.
         // * If there isn't one, then we pick the next argument (we can do it without checking
         //   anything since named arguments are always last).
-        let mut allow_positional = true;
-        for (index, arg) in def.args.iter().enumerate() {
-            let expr = match named_arguments.get(&arg) {
-                Some(expr) => {
-                    allow_positional = false;
-                    expr
+        // * If there are no more unnamed arguments, we read the field of the same name from the
+        //   spread argument, or fall back to the default value of the parameter.
+        let mut missing = Vec::new();
+        let mut types = Vec::new();
+        let mut field;
+        for (
+            index,
+            MacroArg {
+                name: arg,
+                ty,
+                default,
+            },
+        ) in def.args.iter().enumerate()
+        {
+            let expr = match (named_arguments.get(arg), spread, default) {
+                (Some(_), _, _) if index < positional => {
+                    return Err(CompileError::from(format!(
+                        "argument `{arg}` was passed both by position and by name in macro \
+                         {name:?}"
+                    )));
                 }
-                None => {
-                    if !allow_positional {
-                        // If there is already at least one named argument, then it's not allowed
-                        // to use unnamed ones at this point anymore.
-                        return Err(CompileError::from(format!(
-                            "cannot have unnamed argument (`{arg}`) after named argument in macro \
-                             {name:?}"
-                        )));
-                    }
-                    &args[index]
+                (Some(expr), _, _) => *expr,
+                (None, _, _) if index < positional => &args[index],
+                (None, Some(spread), _) => {
+                    field = Expr::Attr(Box::new(spread.clone()), arg);
+                    &field
+                }
+                (None, None, Some(default)) => default,
+                (None, None, None) => {
+                    missing.push(format!("`{arg}`"));
+                    continue;
                 }
             };
             match expr {
                 // If `expr` is already a form of variable then
                 // don't reintroduce a new variable. This is
                 // to avoid moving non-copyable values.
-                &Expr::Var(name) if name != "self" => {
+                // A typed parameter is checked by reference, so that rustc
+                // checks the argument against the declared type.
+                &Expr::Var(name) if name != "self" && function.is_none() => {
                     let var = self.locals.resolve_or_self(name);
+                    if let Some(ty) = ty {
+                        buf.writeln(&type_check(ty, &var))?;
+                    }
                     self.locals.insert(arg, LocalMeta::with_ref(var));
                 }
-                Expr::Attr(obj, attr) => {
+                Expr::Attr(obj, attr) if function.is_none() => {
                     let mut attr_buf = Buffer::new(0);
                     self.visit_attr(&mut attr_buf, obj, attr)?;
 
                     let var = self.locals.resolve(&attr_buf.buf).unwrap_or(attr_buf.buf);
+                    if let Some(ty) = ty {
+                        buf.writeln(&type_check(ty, &var))?;
+                    }
                     self.locals.insert(arg, LocalMeta::with_ref(var));
                 }
                 // Everything else still needs to become variables,
                 // to avoid having the same logic be executed
                 // multiple times, e.g. in the case of macro
                 // parameters being used multiple times.
+                // Typed parameters are bound with their type, and the
+                // parameters of functions are passed by value, so
+                // variables are cloned rather than moved, or borrowed
+                // for a reference type.
                 _ => {
                     if is_first_variable {
                         is_first_variable = false
@@ -806,17 +993,57 @@ impl<'a> Generator<'a> {
                         values.write(", ");
                     }
                     names.write(arg);
-
-                    values.write("(");
-                    values.write(&self.visit_expr_root(expr)?);
-                    values.write(")");
+                    types.push(ty.unwrap_or("_"));
+
+                    let value = self.visit_expr_root(expr)?;
+                    match expr {
+                        Expr::Var(_) | Expr::Attr(..)
+                            if function.is_some() && ty.is_some_and(is_ref_type) =>
+                        {
+                            values.write(&format!("&({value})"));
+                        }
+                        Expr::Var(_) | Expr::Attr(..) if function.is_some() => {
+                            values.write(&format!("::std::clone::Clone::clone(&({value}))"));
+                        }
+                        _ => values.write(&format!("({value})")),
+                    }
                     self.locals.insert_with_default(arg);
                 }
             }
         }
+        if !missing.is_empty() {
+            return Err(CompileError::from(format!(
+                "macro {name:?} is missing required argument{} {}",
+                if missing.len() != 1 { "s" } else { "" },
+                missing.join(", ")
+            )));
+        }
 
         debug_assert_eq!(names.buf.is_empty(), values.buf.is_empty());
-        if !names.buf.is_empty() {
+        if let Some(index) = function {
+            buf.writeln(&format!(
+                "self.{}(&mut *writer{}{})?;",
+                function_name(index, name),
+                if values.buf.is_empty() { "" } else { ", " },
+                values.buf
+            ))?;
+
+            self.flush_ws(def.ws2);
+            buf.writeln("}")?;
+            self.locals.pop();
+            self.callers.pop();
+            self.prepare_ws(ws);
+            return Ok(0);
+        }
+
+        if types.iter().any(|ty| *ty != "_") {
+            buf.writeln(&format!(
+                "let ({},): ({},) = ({},);",
+                names.buf,
+                types.join(", "),
+                values.buf
+            ))?;
+        } else if !names.buf.is_empty() {
             buf.writeln(&format!("let ({}) = ({});", names.buf, values.buf))?;
         }
 
@@ -826,27 +1053,49 @@ impl<'a> Generator<'a> {
         size_hint += self.write_buf_writable(buf)?;
         buf.writeln("}")?;
         self.locals.pop();
+        self.callers.pop();
         self.prepare_ws(ws);
         Ok(size_hint)
     }
 
+    // If `slot` is `Some`, this renders the named slot passed to the current macro call,
+    // or nothing if the caller did not provide it. Otherwise this renders the caller body.
     fn write_caller(
         &mut self,
-        ctx: &'a Context<'_>,
         buf: &mut Buffer,
         outer: Ws,
+        slot: Option<&str>,
     ) -> Result<usize, CompileError> {
         self.flush_ws(outer);
 
-        let caller = match self.caller_node {
-            Some(caller) => caller,
-            None => return Err("caller() can only be used inside a macro".into()),
+        // The caller body belongs to the calling template, where `caller()` is the body of
+        // the call of the macro it is written in, if any.
+        let (caller, ctx) = match (self.callers.pop(), self.function) {
+            (Some(caller), _) => caller,
+            (None, Some(name)) => {
+                return Err(format!("caller() cannot be used in recursive macro {name:?}").into())
+            }
+            (None, None) => return Err("caller() can only be used inside a macro".into()),
         };
 
-        let size_hint = self.handle(ctx, &caller.nodes, buf, AstLevel::Nested)?;
-        self.flush_ws(caller.ws);
+        let size_hint = match slot {
+            None => {
+                let size_hint = self.handle(ctx, &caller.nodes, buf, AstLevel::Nested)?;
+                self.flush_ws(caller.ws);
+                size_hint
+            }
+            Some(name) => match find_slot(caller, name) {
+                Some(slot) => {
+                    self.prepare_ws(slot.ws1);
+                    let size_hint = self.handle(ctx, &slot.nodes, buf, AstLevel::Nested)?;
+                    self.flush_ws(slot.ws2);
+                    size_hint
+                }
+                None => 0,
+            },
+        };
 
-        self.caller_node = None;
+        self.callers.push((caller, ctx));
         self.prepare_ws(outer);
         Ok(size_hint)
     }
@@ -880,20 +1129,26 @@ impl<'a> Generator<'a> {
 
         let locals = MapChain::with_parent(&self.locals);
         let mut child = Self::new(self.input, self.contexts, self.heritage, locals);
+        child.functions = mem::take(&mut self.functions);
+        child.function = self.function;
 
-        let nodes = match self.contexts.get(path.as_path()) {
-            Some(ctx) => ctx.nodes,
-            None => match self.includes.entry(path) {
-                Entry::Occupied(entry) => entry.into_mut().nodes(),
-                Entry::Vacant(entry) => {
-                    let src = get_template_source(entry.key())?;
-                    entry.insert(Parsed::new(src, self.input.syntax)?).nodes()
-                }
-            },
+        // Included templates have their own context, with the imports of their components.
+        let mut size_hint = match self.contexts.get(path.as_path()) {
+            Some(include) => child.handle(include, include.nodes, buf, AstLevel::Top)?,
+            None => {
+                let nodes = match self.includes.entry(path) {
+                    Entry::Occupied(entry) => entry.into_mut().nodes(),
+                    Entry::Vacant(entry) => {
+                        let parsed =
+                            parse_include(entry.key(), self.input.config, self.input.syntax)?;
+                        entry.insert(parsed).nodes()
+                    }
+                };
+                child.handle(ctx, nodes, buf, AstLevel::Nested)?
+            }
         };
-
-        let mut size_hint = child.handle(ctx, nodes, buf, AstLevel::Nested)?;
         size_hint += child.write_buf_writable(buf)?;
+        self.functions = child.functions;
         self.prepare_ws(i.ws);
 
         Ok(size_hint)
@@ -1177,6 +1432,9 @@ impl<'a> Generator<'a> {
             Expr::Try(ref expr) => self.visit_try(buf, expr.as_ref())?,
             Expr::Tuple(ref exprs) => self.visit_tuple(buf, exprs)?,
             Expr::NamedArgument(_, ref expr) => self.visit_named_argument(buf, expr)?,
+            Expr::Spread(_) => {
+                return Err("spread arguments are only allowed in macro calls".into());
+            }
         })
     }
 
@@ -1468,6 +1726,18 @@ impl<'a> Generator<'a> {
         args: &[Expr<'_>],
     ) -> Result<DisplayWrap, CompileError> {
         match left {
+            // `caller.has("name")` tells whether the current macro call provides the slot `name`.
+            Expr::Attr(left, "has")
+                if **left == Expr::Var("caller") && !self.callers.is_empty() =>
+            {
+                let (caller, _) = self.callers[self.callers.len() - 1];
+                match args {
+                    [Expr::StrLit(name)] => {
+                        buf.write(&find_slot(caller, name).is_some().to_string());
+                    }
+                    _ => return Err("caller.has(…) expects the name of a slot as string".into()),
+                }
+            }
             Expr::Attr(left, method) if **left == Expr::Var("loop") => match *method {
                 "cycle" => match args {
                     [arg] => {
@@ -1943,6 +2213,128 @@ impl<'a, K: Eq + hash::Hash, V> Default for MapChain<'a, K, V> {
     }
 }
 
+/// A macro, by the path of the template defining it and its name.
+type MacroKey<'a> = (&'a Path, &'a str);
+
+fn function_name(index: usize, name: &str) -> String {
+    format!("__jrsx_macro_{index}_{name}")
+}
+
+/// Returns the cycle of calls through which the macro `start` calls itself, if any.
+fn find_recursion<'a>(
+    contexts: &'a HashMap<&'a Path, Context<'a>>,
+    start: MacroKey<'a>,
+) -> Option<Vec<MacroKey<'a>>> {
+    let mut visited = HashSet::new();
+    let mut stack = vec![vec![start]];
+
+    while let Some(calls) = stack.pop() {
+        for callee in macro_callees(contexts, *calls.last().unwrap()) {
+            let mut calls = calls.clone();
+            calls.push(callee);
+            if callee == start {
+                return Some(calls);
+            }
+            if visited.insert(callee) {
+                stack.push(calls);
+            }
+        }
+    }
+
+    None
+}
+
+/// Lists the macros called from the body of the macro `key`, including from the bodies
+/// of its own calls.
+fn macro_callees<'a>(
+    contexts: &'a HashMap<&'a Path, Context<'a>>,
+    (path, name): MacroKey<'a>,
+) -> Vec<MacroKey<'a>> {
+    let ctx = &contexts[path];
+    let mut callees = Vec::new();
+    let mut nested = vec![&ctx.macros[name].nodes[..]];
+
+    while let Some(nodes) = nested.pop() {
+        for node in nodes {
+            match node {
+                Node::Call(call) => {
+                    nested.push(&call.nodes);
+                    let path = match call.scope {
+                        Some("caller") => continue,
+                        Some(scope) => match ctx
+                            .imports
+                            .get(scope)
+                            .and_then(|path| contexts.get_key_value(path.as_path()))
+                        {
+                            Some((path, _)) => *path,
+                            None => continue,
+                        },
+                        None => path,
+                    };
+                    if contexts[path].macros.contains_key(call.name) {
+                        callees.push((path, call.name));
+                    }
+                }
+                Node::Slot(slot) => nested.push(&slot.nodes),
+                Node::If(i) => nested.extend(i.branches.iter().map(|cond| &cond.nodes[..])),
+                Node::Loop(l) => nested.extend([&l.body[..], &l.else_nodes[..]]),
+                Node::Match(m) => nested.extend(m.arms.iter().map(|arm| &arm.nodes[..])),
+                Node::BlockDef(b) => nested.push(&b.nodes),
+                _ => {}
+            }
+        }
+    }
+
+    callees
+}
+
+/// Checks that `var` can be passed as a parameter of type `ty`, without moving it. A reference
+/// type is checked as is, so that `&String` coerces to `&str` like in a function call.
+fn type_check(ty: &str, var: &str) -> String {
+    if is_ref_type(ty) {
+        format!("let _: {ty} = &({var});")
+    } else {
+        format!("let _: &{ty} = &({var});")
+    }
+}
+
+fn is_ref_type(ty: &str) -> bool {
+    ty.starts_with('&')
+}
+
+fn find_slot<'a>(caller: &'a Call<'_>, name: &str) -> Option<&'a Slot<'a>> {
+    caller.nodes.iter().find_map(|node| match node {
+        Node::Slot(slot) if slot.name == name => Some(slot),
+        _ => None,
+    })
+}
+
+/// Finds a slot which is not at the top level of the body of `caller`, like one inside
+/// `{% if %}`. Slots of nested calls belong to those calls.
+fn find_nested_slot<'a>(caller: &'a Call<'_>) -> Option<&'a Slot<'a>> {
+    let mut nested = Vec::new();
+    let push_children = |nested: &mut Vec<&'a [Node<'a>]>, node: &'a Node<'a>| match node {
+        Node::Slot(slot) => nested.push(&slot.nodes),
+        Node::If(i) => nested.extend(i.branches.iter().map(|cond| &cond.nodes[..])),
+        Node::Loop(l) => nested.extend([&l.body[..], &l.else_nodes[..]]),
+        Node::Match(m) => nested.extend(m.arms.iter().map(|arm| &arm.nodes[..])),
+        _ => {}
+    };
+    for node in &caller.nodes {
+        push_children(&mut nested, node);
+    }
+
+    while let Some(nodes) = nested.pop() {
+        for node in nodes {
+            if let Node::Slot(slot) = node {
+                return Some(slot);
+            }
+            push_children(&mut nested, node);
+        }
+    }
+    None
+}
+
 /// Returns `true` if enough assumptions can be made,
 /// to determine that `self` is copyable.
 fn is_copyable(expr: &Expr<'_>) -> bool {
@@ -2000,12 +2392,12 @@ pub(crate) fn is_cacheable(expr: &Expr<'_>) -> bool {
         Expr::Unary(_, arg) => is_cacheable(arg),
         Expr::BinOp(_, lhs, rhs) => is_cacheable(lhs) && is_cacheable(rhs),
         Expr::Range(_, lhs, rhs) => {
-            lhs.as_ref().map_or(true, |v| is_cacheable(v))
-                && rhs.as_ref().map_or(true, |v| is_cacheable(v))
+            lhs.iter().all(|v| is_cacheable(v)) && rhs.iter().all(|v| is_cacheable(v))
         }
         Expr::Group(arg) => is_cacheable(arg),
         Expr::Tuple(args) => args.iter().all(is_cacheable),
         Expr::NamedArgument(_, expr) => is_cacheable(expr),
+        Expr::Spread(expr) => is_cacheable(expr),
         // We have too little information to tell if the expression is pure:
         Expr::Call(_, _) => false,
         Expr::RustMacro(_, _) => false,
@@ -36,6 +36,7 @@ impl Heritage<'_> {
 type BlockAncestry<'a> = HashMap<&'a str, Vec<(&'a Context<'a>, &'a BlockDef<'a>)>>;
 
 pub(crate) struct Context<'a> {
+    pub(crate) path: &'a Path,
     pub(crate) nodes: &'a [Node<'a>],
     pub(crate) extends: Option<PathBuf>,
     pub(crate) blocks: HashMap<&'a str, &'a BlockDef<'a>>,
@@ -46,7 +47,7 @@ pub(crate) struct Context<'a> {
 impl Context<'_> {
     pub(crate) fn new<'n>(
         config: &Config<'_>,
-        path: &Path,
+        path: &'n Path,
         nodes: &'n [Node<'n>],
     ) -> Result<Context<'n>, CompileError> {
         let mut extends = None;
@@ -95,6 +96,9 @@ impl Context<'_> {
                             nested.push(&arm.nodes);
                         }
                     }
+                    // Blocks may be passed to components, like the content of a layout.
+                    Node::Call(c) => nested.push(&c.nodes),
+                    Node::Slot(s) => nested.push(&s.nodes),
                     _ => {}
                 }
             }
@@ -102,6 +106,7 @@ impl Context<'_> {
         }
 
         Ok(Context {
+            path,
             nodes,
             extends,
             blocks,
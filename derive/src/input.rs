@@ -1,12 +1,16 @@
 use std::collections::hash_map::HashMap;
+use std::collections::HashSet;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
 use mime::Mime;
 use quote::ToTokens;
+use syn::ext::IdentExt;
 use syn::punctuated::Punctuated;
 
-use crate::config::{get_template_source, read_config_file, Config};
+use crate::config::{
+    parse_include, parse_rewritten, parse_template, read_config_file, rewrite_source, Config,
+};
 use crate::CompileError;
 use parser::{Node, Parsed, Syntax};
 
@@ -47,7 +51,15 @@ impl TemplateInput<'_> {
             .as_ref()
             .expect("template path or source not found in attributes");
         let path = match (&source, &ext) {
-            (Source::Path(path), _) => config.find_template(path, None)?,
+            // The template file is imported and called from a generated source, which gets
+            // a synthetic `path` just like a `source` template, with the extension of the file.
+            (Source::Path(path), _) => {
+                config.find_template(path, None)?;
+                match ext_default_to_path(ext.as_deref(), Path::new(path)) {
+                    Some(ext) => PathBuf::from(format!("{}.{}", ast.ident, ext)),
+                    None => PathBuf::from(ast.ident.to_string()),
+                }
+            }
             (&Source::Source(_), Some(ext)) => PathBuf::from(format!("{}.{}", ast.ident, ext)),
             (&Source::Source(_), None) => {
                 return Err("must include 'ext' attribute when using 'source' attribute".into())
@@ -104,15 +116,61 @@ impl TemplateInput<'_> {
         &self,
         map: &mut HashMap<PathBuf, Parsed>,
     ) -> Result<(), CompileError> {
-        let source = match &self.source {
-            Source::Source(s) => s.clone(),
-            Source::Path(_) => get_template_source(&self.path)?,
+        // The root template is a component, called with the fields of the struct as props,
+        // unless it uses inheritance.
+        let mut root_file = None;
+        let parsed = match &self.source {
+            Source::Source(source) => {
+                let (rewritten, source_map) =
+                    rewrite_source(&self.path, source, true, false, self.config, self.syntax)?;
+                let parse = |rewritten| {
+                    parse_rewritten(
+                        &self.path,
+                        source,
+                        true,
+                        rewritten,
+                        &source_map,
+                        self.syntax,
+                    )
+                };
+                let parsed = parse(rewritten.clone())?;
+                match self.root_props(&parsed, &self.path)? {
+                    Some(props) => {
+                        let props = props.iter().map(String::as_str).collect::<Vec<_>>();
+                        let call = rewriter::transform_source(&self.path, &props, self.syntax);
+                        parse(rewritten + &call)?
+                    }
+                    None => parsed,
+                }
+            }
+            Source::Path(path) => {
+                let file = self.config.find_template(path, None)?;
+                let parsed = parse_template(&file, self.config, self.syntax)?;
+                let source = match self.root_props(&parsed, Path::new(path))? {
+                    Some(props) => {
+                        let props = props.iter().map(String::as_str).collect::<Vec<_>>();
+                        rewriter::transform_path(path, &props, self.syntax)
+                    }
+                    None => rewriter::transform_extends(path, self.syntax),
+                };
+                // The file is imported, or extended, by the generated source: it is not
+                // parsed again.
+                root_file = Some((file, parsed));
+                Parsed::new(source, self.syntax)?
+            }
+        };
+        let mut parse_file = |path: &Path| match root_file.take() {
+            Some((file, parsed)) if file == path => Ok(parsed),
+            other => {
+                root_file = other;
+                parse_template(path, self.config, self.syntax)
+            }
         };
 
         let mut dependency_graph = Vec::new();
-        let mut check = vec![(self.path.clone(), source)];
-        while let Some((path, source)) = check.pop() {
-            let parsed = Parsed::new(source, self.syntax)?;
+        let mut included = HashSet::new();
+        let mut check = vec![(self.path.clone(), parsed)];
+        while let Some((path, parsed)) = check.pop() {
             for n in parsed.nodes() {
                 match n {
                     Node::Extends(extends) => {
@@ -129,22 +187,111 @@ impl TemplateInput<'_> {
                             .into());
                         }
                         dependency_graph.push(dependency_path);
-                        let source = get_template_source(&extends)?;
-                        check.push((extends, source));
+                        let parsed = parse_file(&extends)?;
+                        check.push((extends, parsed));
                     }
                     Node::Import(import) => {
                         let import = self.config.find_template(import.path, Some(&path))?;
-                        let source = get_template_source(&import)?;
-                        check.push((import, source));
+                        if included.contains(&import) {
+                            return Err(both_included_and_imported(&import));
+                        }
+                        // Components may import themselves, or each other, to be recursive.
+                        if import == path
+                            || map.contains_key(&import)
+                            || check.iter().any(|(queued, _)| *queued == import)
+                        {
+                            continue;
+                        }
+                        let parsed = parse_file(&import)?;
+                        check.push((import, parsed));
                     }
                     _ => {}
                 }
             }
+
+            // Included templates are rewritten in place, and get a context of their own for
+            // the imports of their components.
+            let mut includes = Vec::new();
+            find_includes(parsed.nodes(), &mut includes);
+            for include in includes {
+                // Includes are found from the root template, like in the generator.
+                let include = self.config.find_template(include, Some(&self.path))?;
+                if included.contains(&include) {
+                    continue;
+                }
+                if include == path
+                    || map.contains_key(&include)
+                    || check.iter().any(|(queued, _)| *queued == include)
+                {
+                    return Err(both_included_and_imported(&include));
+                }
+                let parsed = parse_include(&include, self.config, self.syntax)?;
+                included.insert(include.clone());
+                check.push((include, parsed));
+            }
+
             map.insert(path, parsed);
         }
         Ok(())
     }
 
+    /// Reads the `{#def}` props of the root template, rewritten for `path`, and returns those
+    /// the struct has a field for. A prop without a default value must have a matching field.
+    /// Returns `None` if the template has no component macro, as it uses inheritance.
+    fn root_props(
+        &self,
+        parsed: &Parsed,
+        path: &Path,
+    ) -> Result<Option<Vec<String>>, CompileError> {
+        let macro_name = rewriter::normalize(path);
+        let Some(def) = parsed.nodes().iter().find_map(|node| match node {
+            Node::Macro(m) if m.name == macro_name => Some(m),
+            _ => None,
+        }) else {
+            return Ok(None);
+        };
+
+        let fields = match &self.ast.data {
+            syn::Data::Struct(syn::DataStruct {
+                fields: syn::Fields::Named(fields),
+                ..
+            }) => fields
+                .named
+                .iter()
+                .filter_map(|field| field.ident.as_ref())
+                .map(|ident| ident.unraw().to_string())
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        let mut props = Vec::new();
+        let mut missing = Vec::new();
+        for arg in &def.args {
+            if fields.iter().any(|field| field == arg.name) {
+                props.push(arg.name.to_owned());
+            } else if arg.default.is_none() {
+                missing.push(format!("`{}`", arg.name));
+            }
+        }
+
+        if !missing.is_empty() {
+            let template = match &self.source {
+                Source::Path(path) => format!("template {path:?}"),
+                Source::Source(_) => "template source".to_owned(),
+            };
+            return Err(format!(
+                "{template} declares prop(s) {} in {}def {} with no matching field in `{}`",
+                missing.join(", "),
+                self.syntax.comment_start,
+                self.syntax.comment_end,
+                self.ast.ident,
+            )
+            .into());
+        }
+
+        Ok(Some(props))
+    }
+
     #[inline]
     pub(crate) fn extension(&self) -> Option<&str> {
         ext_default_to_path(self.ext, &self.path)
@@ -219,8 +366,7 @@ impl TemplateArgs {
                     if args.source.is_some() {
                         return Err("must specify 'source' or 'path', not both".into());
                     }
-                    args.source = Some(Source::Source(rewriter::transform_path(s.value())));
-                    args.ext = Some("html".into());
+                    args.source = Some(Source::Path(s.value()));
                 } else {
                     return Err("template path must be string literal".into());
                 }
@@ -282,12 +428,44 @@ impl TemplateArgs {
     }
 }
 
+/// Collects the paths of the templates included by `nodes`, at any depth.
+fn find_includes<'a>(nodes: &'a [Node<'a>], includes: &mut Vec<&'a str>) {
+    for node in nodes {
+        match node {
+            Node::Include(include) => includes.push(include.path),
+            Node::Call(call) => find_includes(&call.nodes, includes),
+            Node::If(i) => {
+                for cond in &i.branches {
+                    find_includes(&cond.nodes, includes);
+                }
+            }
+            Node::Match(m) => {
+                for arm in &m.arms {
+                    find_includes(&arm.nodes, includes);
+                }
+            }
+            Node::Loop(l) => {
+                find_includes(&l.body, includes);
+                find_includes(&l.else_nodes, includes);
+            }
+            Node::BlockDef(b) => find_includes(&b.nodes, includes),
+            Node::Macro(m) => find_includes(&m.nodes, includes),
+            Node::Slot(s) => find_includes(&s.nodes, includes),
+            _ => {}
+        }
+    }
+}
+
+fn both_included_and_imported(path: &Path) -> CompileError {
+    format!("template {path:?} cannot be both included and used as a component").into()
+}
+
 #[inline]
 fn ext_default_to_path<'a>(ext: Option<&'a str>, path: &'a Path) -> Option<&'a str> {
     ext.or_else(|| extension(path))
 }
 
-fn extension(path: &Path) -> Option<&str> {
+pub(crate) fn extension(path: &Path) -> Option<&str> {
     let ext = path.extension().map(|s| s.to_str().unwrap())?;
 
     const JINJA_EXTENSIONS: [&str; 3] = ["j2", "jinja", "jinja2"];
@@ -308,14 +486,23 @@ pub(crate) enum Source {
     Source(String),
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
 pub(crate) enum Print {
     All,
     Ast,
     Code,
+    Jsx,
+    #[default]
     None,
 }
 
+impl Print {
+    /// Tells whether the templates rewritten from JSX are printed, along with the rest for `all`.
+    pub(crate) fn jsx(self) -> bool {
+        matches!(self, Print::Jsx | Print::All)
+    }
+}
+
 impl FromStr for Print {
     type Err = CompileError;
 
@@ -325,18 +512,13 @@ impl FromStr for Print {
             "all" => All,
             "ast" => Ast,
             "code" => Code,
+            "jsx" => Jsx,
             "none" => None,
             v => return Err(format!("invalid value for print option: {v}",).into()),
         })
     }
 }
 
-impl Default for Print {
-    fn default() -> Self {
-        Self::None
-    }
-}
-
 pub(crate) fn extension_to_mime_type(ext: &str) -> Mime {
     let basic_type = mime_guess::from_ext(ext).first_or_octet_stream();
     for (simple, utf_8) in &TEXT_TYPES {
@@ -405,6 +587,162 @@ mod tests {
         assert_eq!(extension(Path::new("foo/bar/baz.txt.jinja2")), Some("txt"));
     }
 
+    #[test]
+    fn test_root_props() {
+        let ast = syn::parse_str(
+            "#[template(path = \"props.html\")] struct Page { title: String, user: String }",
+        )
+        .unwrap();
+        assert!(crate::build_template(&ast).is_ok());
+
+        let ast =
+            syn::parse_str("#[template(path = \"props.html\")] struct Page { title: String }")
+                .unwrap();
+        assert_eq!(
+            crate::build_template(&ast).unwrap_err().to_string(),
+            "template \"props.html\" declares prop(s) `user` in {#def #} with no matching field \
+             in `Page`"
+        );
+    }
+
+    #[test]
+    fn test_macro_args() {
+        let build = |call: &str| {
+            let source = format!(
+                "{{% macro hello(name, title, greeting = \\\"Hi\\\") %}}{{% endmacro %}}{call}"
+            );
+            let ast = syn::parse_str(&format!(
+                "#[template(source = \"{source}\", ext = \"html\")] struct Page {{ user: String }}"
+            ))
+            .unwrap();
+            crate::build_template(&ast).map_err(|err| err.to_string())
+        };
+
+        assert!(build("{% call hello(user, title=user) %}{% endcall %}").is_ok());
+        assert_eq!(
+            build("{% call hello(greeting=user) %}{% endcall %}").unwrap_err(),
+            "macro \"hello\" is missing required arguments `name`, `title`"
+        );
+        assert_eq!(
+            build("{% call hello(user) %}{% endcall %}").unwrap_err(),
+            "macro \"hello\" is missing required argument `title`"
+        );
+        assert_eq!(
+            build("{% call hello(user, user, user, user) %}{% endcall %}").unwrap_err(),
+            "macro \"hello\" expected at most 3 arguments, found 4"
+        );
+        assert_eq!(
+            build("{% call hello(user, name=user) %}{% endcall %}").unwrap_err(),
+            "argument `name` was passed both by position and by name in macro \"hello\""
+        );
+        assert_eq!(
+            build("{% call hello(user, ...user) %}{% endcall %}").unwrap_err(),
+            "spread argument must be passed first in macro \"hello\""
+        );
+    }
+
+    #[test]
+    fn test_inline_source() {
+        let ast = syn::parse_str(
+            "#[template(source = \"{#def title #}<h1>{{ title }}</h1>\", ext = \"html\")] \
+             struct Page { title: String }",
+        )
+        .unwrap();
+        assert!(crate::build_template(&ast).is_ok());
+
+        let ast = syn::parse_str(
+            "#[template(source = \"{#def title #}\", ext = \"html\")] struct Page {}",
+        )
+        .unwrap();
+        assert_eq!(
+            crate::build_template(&ast).unwrap_err().to_string(),
+            "template source declares prop(s) `title` in {#def #} with no matching field in `Page`"
+        );
+
+        let ast = syn::parse_str("#[template(source = \"<Card>\", ext = \"html\")] struct Page {}")
+            .unwrap();
+        assert_eq!(
+            crate::build_template(&ast).unwrap_err().to_string(),
+            "unable to rewrite template source: unclosed tag <Card> at row 1, column 0"
+        );
+    }
+
+    #[test]
+    fn test_nested_slot() {
+        let ast = syn::parse_str(
+            "#[template(source = \"<Card>{% if true %}<Card:header>Hi</Card:header>{% endif %}\
+             </Card>\", ext = \"html\")] struct Page {}",
+        )
+        .unwrap();
+        assert_eq!(
+            crate::build_template(&ast).unwrap_err().to_string(),
+            "slot \"header\" must be at the top level of the call of macro \"card\""
+        );
+    }
+
+    #[test]
+    fn test_inheritance_component() {
+        let ast =
+            syn::parse_str("#[template(source = \"<Base />\", ext = \"html\")] struct Page {}")
+                .unwrap();
+        assert_eq!(
+            crate::build_template(&ast).unwrap_err().to_string(),
+            "template \"base.html\" uses `extends` or `block` tags and cannot be used as a \
+             component"
+        );
+    }
+
+    #[test]
+    fn test_recursive_macro() {
+        let ast = syn::parse_str(
+            "#[template(source = \"<Recursive node=root />\", ext = \"html\")] \
+             struct Page { root: u32 }",
+        )
+        .unwrap();
+        assert_eq!(
+            crate::build_template(&ast).unwrap_err().to_string(),
+            "recursive macro \"recursive\" must declare the type of its parameter `node`, \
+             in call cycle `recursive.html::recursive` -> `recursive.html::recursive`"
+        );
+    }
+
+    #[test]
+    fn test_include() {
+        let ast = syn::parse_str(
+            "#[template(source = \"{% include \\\"a.html\\\" %}\", ext = \"html\")] struct Page {}",
+        )
+        .unwrap();
+        // The included file is rendered in place.
+        assert!(crate::build_template(&ast)
+            .unwrap()
+            .contains("writer.write_str(\"foo\")?;"));
+
+        let ast = syn::parse_str(
+            "#[template(source = \"{% include \\\"a.html\\\" %}<A />\", ext = \"html\")] \
+             struct Page {}",
+        )
+        .unwrap();
+        let path = Config::new("", None).unwrap().dirs[0].join("a.html");
+        assert_eq!(
+            crate::build_template(&ast).unwrap_err().to_string(),
+            format!("template {path:?} cannot be both included and used as a component")
+        );
+    }
+
+    #[test]
+    fn test_print() {
+        assert_eq!(Print::from_str("jsx").unwrap(), Print::Jsx);
+        assert_eq!(Print::from_str("all").unwrap(), Print::All);
+        assert!(Print::Jsx.jsx());
+        assert!(Print::All.jsx());
+        assert!(!Print::Code.jsx());
+        assert!(!Print::default().jsx());
+        assert_eq!(
+            Print::from_str("rewritten").unwrap_err().to_string(),
+            "invalid value for print option: rewritten"
+        );
+    }
+
     #[test]
     fn test_only_jinja_ext() {
         assert_eq!(extension(Path::new("foo-bar.j2")), Some("j2"));
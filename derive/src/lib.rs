@@ -58,6 +58,15 @@ pub(crate) fn build_template(ast: &syn::DeriveInput) -> Result<String, CompileEr
         None
     };
 
+    if input.print.jsx() {
+        // The root template first, then the templates it uses, as rewritten from JSX.
+        let mut paths = templates.keys().collect::<Vec<_>>();
+        paths.sort_by_key(|path| (*path != &input.path, *path));
+        for path in paths {
+            eprintln!("// {}\n{}", path.display(), templates[path].source());
+        }
+    }
+
     if input.print == Print::Ast || input.print == Print::All {
         eprintln!("{:#?}", templates[input.path.as_path()].nodes());
     }
@@ -29,3 +29,405 @@ fn test_template2() {
         <div>\nSuper!\n</div>\n"
     );
 }
+
+#[derive(Template)]
+#[template(path = "expr.html")]
+struct Expr<'a> {
+    name: &'a str,
+}
+
+#[test]
+fn test_template_expr() {
+    assert_eq!(
+        Expr { name: "world" }.to_string(),
+        "\n\n\n\n\
+        <h1>Hello, WORLD!</h1>\n\n\n\n\
+        <h1>Hello, 6!</h1>\n"
+    );
+}
+
+#[derive(Template)]
+#[template(path = "namespace.html")]
+struct Namespace {}
+
+#[test]
+fn test_template_namespace() {
+    assert_eq!(Namespace {}.to_string(), "\n\n<button>Ok</button>\n");
+}
+
+struct User<'a> {
+    name: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "spread.html")]
+struct Spread<'a> {
+    name: &'a str,
+    user: User<'a>,
+}
+
+#[test]
+fn test_template_spread() {
+    assert_eq!(
+        Spread {
+            name: "world",
+            user: User { name: "user" }
+        }
+        .to_string(),
+        "\n\n\n\n\
+        <h1>Hello, user!</h1>\n\n\n\n\
+        <h1>Hello, world!</h1>\n\n\n\n\
+        <h1>Hello, you!</h1>\n"
+    );
+}
+
+#[derive(Template)]
+#[template(path = "slots.html")]
+struct Slots<'a> {
+    name: &'a str,
+}
+
+#[test]
+fn test_template_slots() {
+    assert_eq!(
+        Slots { name: "world" }.to_string(),
+        "\n\n<div><h2>\n\n\n<h1>Hello, world!</h1></h2>Body</div>\n\n\
+        <div>Body only</div>\n"
+    );
+}
+
+mod caller {
+    pub(crate) const GREETING: &str = "Hi";
+}
+
+#[derive(Template)]
+#[template(source = "<CallerPath />", ext = "html")]
+struct CallerPath;
+
+#[test]
+fn test_template_caller_path() {
+    assert_eq!(CallerPath.to_string(), "\n\n\n<p>Hi</p>\n");
+}
+
+#[derive(Template)]
+#[template(source = "<Panel><p>{{ text }}</p></Panel>", ext = "html")]
+struct NestedCaller<'a> {
+    text: &'a str,
+}
+
+#[test]
+fn test_template_nested_caller() {
+    assert_eq!(
+        NestedCaller { text: "Hi" }.to_string(),
+        "\n\n\n<section>\n<div><p>Hi</p></div></section>\n"
+    );
+}
+
+#[derive(Template)]
+#[template(path = "props.html")]
+struct Props<'a> {
+    name: &'a str,
+}
+
+#[test]
+fn test_template_props() {
+    assert_eq!(
+        Props { name: "world" }.to_string(),
+        "\n\n\nHi, world!\n\n\nHello, world!\n"
+    );
+}
+
+#[derive(Template)]
+#[template(path = "typed.html")]
+struct Typed<'a> {
+    name: &'a str,
+    items: Vec<u32>,
+}
+
+#[test]
+fn test_template_typed() {
+    let typed = Typed {
+        name: "items",
+        items: vec![1, 2, 3],
+    };
+    assert_eq!(typed.to_string(), "\n\n\nitems: 3\n\n\nnone: 0\n");
+}
+
+#[derive(Template)]
+#[template(
+    source = "<Greet name=who count=count />{% for who in names %}<Greet name=who />{% endfor %}",
+    ext = "html"
+)]
+struct TypedOwned {
+    who: String,
+    count: usize,
+    names: Vec<String>,
+}
+
+#[test]
+fn test_template_typed_owned() {
+    let typed = TypedOwned {
+        who: "world".to_owned(),
+        count: 2,
+        names: vec!["jrsx".to_owned()],
+    };
+    assert_eq!(
+        typed.to_string(),
+        "\n\n\n\n<p>Hello world x2</p>\n\n<p>Hello jrsx</p>\n"
+    );
+}
+
+#[derive(Template)]
+#[template(source = "<Label label=name /><Label label=\"none\" />", ext = "html")]
+struct TypedCoerced {
+    name: String,
+}
+
+#[test]
+fn test_template_typed_coerced() {
+    let typed = TypedCoerced {
+        name: "Name".to_owned(),
+    };
+    assert_eq!(
+        typed.to_string(),
+        "\n\n\n\n<label>Name</label>\n\n<label>none</label>\n"
+    );
+}
+
+#[derive(Template)]
+#[template(path = "custom/page.html", syntax = "brackets")]
+struct CustomSyntax {}
+
+#[test]
+fn test_template_syntax() {
+    assert_eq!(CustomSyntax {}.to_string(), "\n\n\n<span>new</span>\n");
+}
+
+#[derive(Template)]
+#[template(path = "pages/dashboard.html")]
+struct Dashboard {}
+
+#[test]
+fn test_template_components() {
+    assert_eq!(
+        Dashboard {}.to_string(),
+        "\n\n\n<p role=\"alert\">Saved</p>\n"
+    );
+}
+
+#[derive(Template)]
+#[template(path = "kebab.html", config = "kebab.toml")]
+struct Kebab {}
+
+#[test]
+fn test_template_naming() {
+    assert_eq!(
+        Kebab {}.to_string(),
+        "\n\n\n<span class=\"status\">ok</span>\n"
+    );
+}
+
+#[derive(Template)]
+#[template(path = "pages/profile.html")]
+struct Profile {}
+
+#[test]
+fn test_template_component_blocks() {
+    assert_eq!(
+        Profile {}.to_string(),
+        "\n\n\n\n<div class=\"badges\">\n\n\n<span class=\"badge info\">new</span>\n\n\n\n\
+         <span class=\"pill\">admin</span>\n\n</div>\n"
+    );
+}
+
+#[derive(Template)]
+#[template(path = "pages/article.html")]
+struct Article<'a> {
+    title: &'a str,
+    author: &'a str,
+}
+
+#[test]
+fn test_template_root_props() {
+    let article = Article {
+        title: "Hello",
+        author: "jrsx",
+    };
+    assert_eq!(article.to_string(), "\n\n<h1>Hello</h1>\n<p>by jrsx</p>\n");
+}
+
+#[derive(Template)]
+#[template(path = "pages/article.html")]
+struct DraftArticle<'a> {
+    title: &'a str,
+    author: &'a str,
+    draft: bool,
+}
+
+#[test]
+fn test_template_root_props_default() {
+    let article = DraftArticle {
+        title: "Hello",
+        author: "jrsx",
+        draft: true,
+    };
+    assert_eq!(
+        article.to_string(),
+        "\n\n<h1>Hello</h1>\n<p>by jrsx (draft)</p>\n"
+    );
+}
+
+#[derive(Template)]
+#[template(source = "{#def title #}<h1>{{ title }}</h1>", ext = "html")]
+struct InlineProps<'a> {
+    title: &'a str,
+}
+
+#[test]
+fn test_template_inline_props() {
+    assert_eq!(
+        InlineProps { title: "Hello" }.to_string(),
+        "\n\n<h1>Hello</h1>\n"
+    );
+}
+
+#[derive(Template)]
+#[template(path = "pages/post.html")]
+struct Post<'a> {
+    title: &'a str,
+    author: &'a str,
+}
+
+#[test]
+fn test_template_inheritance() {
+    let post = Post {
+        title: "Hello",
+        author: "jrsx",
+    };
+    assert_eq!(
+        post.to_string(),
+        "<title>Hello</title>\n\n<div>\n\n\n<h1>Hello, jrsx!</h1></div>"
+    );
+}
+
+#[derive(Template)]
+#[template(path = "signup.html")]
+struct Signup<'a> {
+    title: &'a str,
+}
+
+#[test]
+fn test_template_local_macros() {
+    assert_eq!(
+        Signup { title: "Sign up" }.to_string(),
+        "\n\n\n\n<h1>Sign up <span class=\"badge\">new</span></h1>\n\
+         <label>Email <input></label>\n"
+    );
+}
+
+#[derive(Template)]
+#[template(path = "pages/home.html")]
+struct Home<'a> {
+    links: &'a [(&'a str, &'a str)],
+}
+
+#[test]
+fn test_template_include() {
+    let links = [("/", "Home"), ("/about", "About")];
+    assert_eq!(
+        Home { links: &links }.to_string(),
+        "\n\n<nav>\n\n<a href=\"/\">Home</a>\n\n<a href=\"/about\">About</a></nav>\n\
+         <main>Home</main>\n"
+    );
+}
+
+#[derive(Template)]
+#[template(path = "emails/welcome.txt")]
+struct Welcome<'a> {
+    name: &'a str,
+}
+
+#[test]
+fn test_template_extension() {
+    assert_eq!(
+        <Welcome<'_> as askama::Template>::MIME_TYPE,
+        "text/plain; charset=utf-8"
+    );
+    assert_eq!(
+        Welcome { name: "<jrsx>" }.to_string(),
+        "\n\nHello <jrsx>, welcome!\n\n-- Tom & Jerry\n"
+    );
+}
+
+#[derive(Template)]
+#[template(source = "{#def name #}<Hello name />", ext = "html")]
+struct InlineSource<'a> {
+    name: &'a str,
+}
+
+#[test]
+fn test_template_source() {
+    assert_eq!(
+        InlineSource { name: "inline" }.to_string(),
+        "\n\n\n\n\n<h1>Hello, inline!</h1>\n"
+    );
+}
+
+struct Node {
+    name: &'static str,
+    children: Vec<Node>,
+}
+
+#[derive(Template)]
+#[template(path = "tree.html")]
+struct Tree<'a> {
+    root: &'a Node,
+}
+
+#[test]
+fn test_template_recursive() {
+    let leaf = |name| Node {
+        name,
+        children: vec![],
+    };
+    let root = Node {
+        name: "a",
+        children: vec![
+            Node {
+                name: "b",
+                children: vec![leaf("c")],
+            },
+            leaf("d"),
+        ],
+    };
+    assert_eq!(
+        Tree { root: &root }.to_string(),
+        "\n<ul>\n\n<li>a<ul>\n\n<li>b<ul>\n\n<li>c</li></ul></li>\n\n<li>d</li></ul></li></ul>\n"
+    );
+}
+
+struct MenuItem {
+    label: &'static str,
+    children: Vec<MenuItem>,
+}
+
+#[derive(Template)]
+#[template(path = "menu.html")]
+struct Menu<'a> {
+    items: &'a [MenuItem],
+}
+
+#[test]
+fn test_template_mutually_recursive() {
+    let items = [MenuItem {
+        label: "File",
+        children: vec![MenuItem {
+            label: "Open",
+            children: vec![],
+        }],
+    }];
+    assert_eq!(
+        Menu { items: &items }.to_string(),
+        "\n\n<ul>\n\n<li>File\n\n<ul>\n\n<li>Open</li></ul></li></ul>\n"
+    );
+}
@@ -64,6 +64,7 @@ pub enum Expr<'a> {
     Index(Box<Expr<'a>>, Box<Expr<'a>>),
     Filter(&'a str, Vec<Expr<'a>>),
     NamedArgument(&'a str, Box<Expr<'a>>),
+    Spread(Box<Expr<'a>>),
     Unary(&'a str, Box<Expr<'a>>),
     BinOp(&'a str, Box<Expr<'a>>, Box<Expr<'a>>),
     Range(&'a str, Option<Box<Expr<'a>>>, Option<Box<Expr<'a>>>),
@@ -105,6 +106,7 @@ impl<'a> Expr<'a> {
                                     is_template_macro,
                                 )
                             },
+                            move |i| Self::spread(i, level, is_template_macro),
                             move |i| Self::parse(i, level),
                         ))(i)?;
                         if has_named_arguments && !matches!(expr, Self::NamedArgument(_, _)) {
@@ -152,6 +154,16 @@ impl<'a> Expr<'a> {
         }
     }
 
+    fn spread(i: &'a str, level: Level, is_template_macro: bool) -> ParseResult<'a, Self> {
+        if !is_template_macro {
+            return Err(nom::Err::Error(error_position!(i, ErrorKind::Alt)));
+        }
+
+        let (_, level) = level.nest(i)?;
+        let (i, value) = preceded(ws(tag("...")), cut(move |i| Self::parse(i, level)))(i)?;
+        Ok((i, Self::Spread(Box::new(value))))
+    }
+
     pub(super) fn parse(i: &'a str, level: Level) -> ParseResult<'a, Self> {
         let (_, level) = level.nest(i)?;
         let range_right = move |i| {
@@ -202,10 +214,7 @@ impl<'a> Expr<'a> {
         let mut res = obj;
         for (fname, args) in filters {
             res = Self::Filter(fname, {
-                let mut args = match args {
-                    Some(inner) => inner,
-                    None => Vec::new(),
-                };
+                let mut args = args.unwrap_or_default();
                 args.insert(0, res);
                 args
             });
@@ -31,7 +31,6 @@ mod _parsed {
     pub struct Parsed {
         // `source` must outlive `ast`, so `ast` must be declared before `source`
         ast: Ast<'static>,
-        #[allow(dead_code)]
         source: String,
     }
 
@@ -49,6 +48,11 @@ mod _parsed {
         pub fn nodes(&self) -> &[Node<'_>] {
             &self.ast.nodes
         }
+
+        /// The source the nodes were parsed from.
+        pub fn source(&self) -> &str {
+            &self.source
+        }
     }
 
     impl fmt::Debug for Parsed {
@@ -83,10 +87,34 @@ impl<'a> Ast<'a> {
                 nom::Err::Error(ErrorContext { input, message, .. })
                 | nom::Err::Failure(ErrorContext { input, message, .. }),
             ) => (input, message),
-            Err(nom::Err::Incomplete(_)) => return Err(ParseError("parsing incomplete".into())),
+            Err(nom::Err::Incomplete(_)) => {
+                return Err(ParseError::new(
+                    Some("parsing incomplete".into()),
+                    src,
+                    src.len(),
+                ))
+            }
         };
 
-        let offset = src.len() - input.len();
+        Err(ParseError::new(message, src, src.len() - input.len()))
+    }
+
+    pub fn nodes(&self) -> &[Node<'a>] {
+        &self.nodes
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    message: Option<Cow<'static, str>>,
+    offset: usize,
+    row: usize,
+    column: usize,
+    source_after: String,
+}
+
+impl ParseError {
+    fn new(message: Option<Cow<'static, str>>, src: &str, offset: usize) -> Self {
         let (source_before, source_after) = src.split_at(offset);
 
         let source_after = match source_after.char_indices().enumerate().take(41).last() {
@@ -97,34 +125,39 @@ impl<'a> Ast<'a> {
         let (row, last_line) = source_before.lines().enumerate().last().unwrap_or_default();
         let column = last_line.chars().count();
 
-        let msg = format!(
-            "{}problems parsing template source at row {}, column {} near:\n{}",
-            if let Some(message) = message {
-                format!("{message}\n")
-            } else {
-                String::new()
-            },
-            row + 1,
+        Self {
+            message,
+            offset,
+            row: row + 1,
             column,
             source_after,
-        );
+        }
+    }
 
-        Err(ParseError(msg))
+    /// Byte offset of the error in the parsed source.
+    pub fn offset(&self) -> usize {
+        self.offset
     }
 
-    pub fn nodes(&self) -> &[Node<'a>] {
-        &self.nodes
+    /// Moves the error to `offset` in `src`, e.g. to report it in the source
+    /// a template was generated from.
+    pub fn relocate(self, src: &str, offset: usize) -> Self {
+        Self::new(self.message, src, offset)
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub struct ParseError(String);
-
 impl std::error::Error for ParseError {}
 
 impl fmt::Display for ParseError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        self.0.fmt(f)
+        if let Some(message) = &self.message {
+            writeln!(f, "{message}")?;
+        }
+        write!(
+            f,
+            "problems parsing template source at row {}, column {} near:\n{}",
+            self.row, self.column, self.source_after,
+        )
     }
 }
 
@@ -216,7 +249,7 @@ fn skip_till<'a, O>(
     }
 }
 
-fn keyword<'a>(k: &'a str) -> impl FnMut(&'a str) -> ParseResult<'_> {
+fn keyword<'a>(k: &'a str) -> impl FnMut(&'a str) -> ParseResult<'a> {
     move |i: &'a str| -> ParseResult<'a> {
         let (j, v) = identifier(i)?;
         if k == v {
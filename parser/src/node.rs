@@ -34,6 +34,7 @@ pub enum Node<'a> {
     Include(Include<'a>),
     Import(Import<'a>),
     Macro(Macro<'a>),
+    Slot(Slot<'a>),
     Raw(Raw<'a>),
     Break(Ws),
     Continue(Ws),
@@ -63,6 +64,7 @@ impl<'a> Node<'a> {
                 map(Import::parse, Self::Import),
                 map(|i| BlockDef::parse(i, s), Self::BlockDef),
                 map(|i| Macro::parse(i, s), Self::Macro),
+                map(|i| Slot::parse(i, s), Self::Slot),
                 map(|i| Raw::parse(i, s), Self::Raw),
                 |i| Self::r#break(i, s),
                 |i| Self::r#continue(i, s),
@@ -493,20 +495,29 @@ impl<'a> Loop<'a> {
 pub struct Macro<'a> {
     pub ws1: Ws,
     pub name: &'a str,
-    pub args: Vec<&'a str>,
+    pub args: Vec<MacroArg<'a>>,
     pub nodes: Vec<Node<'a>>,
     pub ws2: Ws,
 }
 
 impl<'a> Macro<'a> {
     fn parse(i: &'a str, s: &State<'_>) -> ParseResult<'a, Self> {
-        fn parameters(i: &str) -> ParseResult<'_, Vec<&str>> {
-            delimited(
-                ws(char('(')),
-                separated_list0(char(','), ws(identifier)),
-                tuple((opt(ws(char(','))), char(')'))),
-            )(i)
-        }
+        let parameter = |i| {
+            let (i, (name, ty, default)) = tuple((
+                ws(identifier),
+                opt(preceded(char(':'), cut(ws(MacroArg::ty)))),
+                opt(preceded(
+                    char('='),
+                    cut(ws(|i| Expr::parse(i, s.level.get()))),
+                )),
+            ))(i)?;
+            Ok((i, MacroArg { name, ty, default }))
+        };
+        let parameters = delimited(
+            ws(char('(')),
+            separated_list0(char(','), parameter),
+            tuple((opt(ws(char(','))), char(')'))),
+        );
 
         let mut start = tuple((
             opt(Whitespace::parse),
@@ -558,6 +569,55 @@ impl<'a> Macro<'a> {
     }
 }
 
+#[derive(Debug, PartialEq)]
+pub struct MacroArg<'a> {
+    pub name: &'a str,
+    pub ty: Option<&'a str>,
+    pub default: Option<Expr<'a>>,
+}
+
+impl<'a> MacroArg<'a> {
+    /// Parses a Rust type up to the next top-level `,`, `=` or `)`.
+    /// The type itself is passed through as is, rustc will check it.
+    fn ty(i: &'a str) -> ParseResult<'a> {
+        let (rest, ty) = Self::split_ty(i, |_| false);
+        if ty.is_empty() {
+            return Err(nom::Err::Error(error_position!(i, ErrorKind::Fail)));
+        }
+        Ok((rest, ty))
+    }
+
+    /// Splits a Rust type off the start of `i`, returning the rest and the trimmed type.
+    /// The type ends at the first `,`, `=` or `)` outside of brackets, or where `stop`
+    /// matches outside of brackets.
+    pub fn split_ty(i: &str, stop: impl Fn(&str) -> bool) -> (&str, &str) {
+        let mut depth = 0usize;
+        let mut prev = None;
+        let mut end = i.len();
+        for (pos, c) in i.char_indices() {
+            match c {
+                '<' | '(' | '[' => depth += 1,
+                // `->` in `fn() -> T` does not close anything.
+                '>' if prev == Some('-') => {}
+                ',' | '=' | ')' if depth == 0 => {
+                    end = pos;
+                    break;
+                }
+                '>' | ')' | ']' => depth = depth.saturating_sub(1),
+                _ if depth == 0 && stop(&i[pos..]) => {
+                    end = pos;
+                    break;
+                }
+                _ => {}
+            }
+            prev = Some(c);
+        }
+
+        let ty = i[..end].trim_end();
+        (&i[ty.len()..], ty)
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub struct Import<'a> {
     pub ws: Ws,
@@ -736,6 +796,55 @@ impl<'a> BlockDef<'a> {
     }
 }
 
+/// A named body passed to a macro call, next to the default `caller()` body.
+#[derive(Debug, PartialEq)]
+pub struct Slot<'a> {
+    pub ws1: Ws,
+    pub name: &'a str,
+    pub nodes: Vec<Node<'a>>,
+    pub ws2: Ws,
+}
+
+impl<'a> Slot<'a> {
+    fn parse(i: &'a str, s: &State<'_>) -> ParseResult<'a, Self> {
+        let mut start = tuple((
+            opt(Whitespace::parse),
+            ws(keyword("slot")),
+            cut(tuple((ws(identifier), opt(Whitespace::parse), |i| {
+                s.tag_block_end(i)
+            }))),
+        ));
+        let (i, (pws1, _, (name, nws1, _))) = start(i)?;
+
+        let mut end = cut(tuple((
+            |i| Node::many(i, s),
+            cut(tuple((
+                |i| s.tag_block_start(i),
+                opt(Whitespace::parse),
+                ws(keyword("endslot")),
+                cut(tuple((
+                    opt(|before| {
+                        let (after, end_name) = ws(identifier)(before)?;
+                        check_end_name(before, after, name, end_name, "slot")
+                    }),
+                    opt(Whitespace::parse),
+                ))),
+            ))),
+        )));
+        let (i, (nodes, (_, pws2, _, (_, nws2)))) = end(i)?;
+
+        Ok((
+            i,
+            Slot {
+                ws1: Ws(pws1, nws1),
+                name,
+                nodes,
+                ws2: Ws(pws2, nws2),
+            },
+        ))
+    }
+}
+
 fn check_end_name<'a>(
     before: &'a str,
     after: &'a str,
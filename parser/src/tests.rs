@@ -1,4 +1,4 @@
-use super::node::{Lit, Whitespace, Ws};
+use super::node::{Call, Lit, Macro, MacroArg, Slot, Whitespace, Ws};
 use super::{Ast, Expr, Node, Syntax};
 
 fn check_ws_split(s: &str, res: &(&str, &str, &str)) {
@@ -789,6 +789,159 @@ fn test_parse_array() {
     );
 }
 
+#[test]
+fn test_parse_call_spread() {
+    let syntax = Syntax::default();
+    assert_eq!(
+        Ast::from_str("{% call hello(...user, name=name) %}{% endcall %}", &syntax)
+            .unwrap()
+            .nodes,
+        vec![Node::Call(Call {
+            ws: Ws(None, None),
+            scope: None,
+            name: "hello",
+            args: vec![
+                Expr::Spread(Box::new(Expr::Var("user"))),
+                Expr::NamedArgument("name", Box::new(Expr::Var("name"))),
+            ],
+            nodes: vec![],
+        })],
+    );
+    assert_eq!(
+        Ast::from_str("{% call hello(...self) %}{% endcall %}", &syntax)
+            .unwrap()
+            .nodes,
+        vec![Node::Call(Call {
+            ws: Ws(None, None),
+            scope: None,
+            name: "hello",
+            args: vec![Expr::Spread(Box::new(Expr::Var("self")))],
+            nodes: vec![],
+        })],
+    );
+    assert!(Ast::from_str("{{ hello(...user) }}", &syntax).is_err());
+}
+
+#[test]
+fn test_parse_slot() {
+    let syntax = Syntax::default();
+    assert_eq!(
+        Ast::from_str(
+            "{% call card() %}{% slot header %}Title{% endslot header %}Body{% endcall %}",
+            &syntax
+        )
+        .unwrap()
+        .nodes,
+        vec![Node::Call(Call {
+            ws: Ws(None, None),
+            scope: None,
+            name: "card",
+            args: vec![],
+            nodes: vec![
+                Node::Slot(Slot {
+                    ws1: Ws(None, None),
+                    name: "header",
+                    nodes: vec![Node::Lit(Lit {
+                        lws: "",
+                        val: "Title",
+                        rws: "",
+                    })],
+                    ws2: Ws(None, None),
+                }),
+                Node::Lit(Lit {
+                    lws: "",
+                    val: "Body",
+                    rws: "",
+                }),
+            ],
+        })],
+    );
+    assert!(Ast::from_str("{% slot header %}{% endslot footer %}", &syntax).is_err());
+}
+
+#[test]
+fn test_parse_macro_defaults() {
+    let syntax = Syntax::default();
+    assert_eq!(
+        Ast::from_str(
+            "{% macro hello(name, greeting = \"Hello\") %}{% endmacro %}",
+            &syntax
+        )
+        .unwrap()
+        .nodes,
+        vec![Node::Macro(Macro {
+            ws1: Ws(None, None),
+            name: "hello",
+            args: vec![
+                MacroArg {
+                    name: "name",
+                    ty: None,
+                    default: None,
+                },
+                MacroArg {
+                    name: "greeting",
+                    ty: None,
+                    default: Some(Expr::StrLit("Hello")),
+                },
+            ],
+            nodes: vec![],
+            ws2: Ws(None, None),
+        })],
+    );
+    assert!(Ast::from_str("{% macro hello(name = ) %}{% endmacro %}", &syntax).is_err());
+}
+
+#[test]
+fn test_parse_macro_types() {
+    let syntax = Syntax::default();
+    assert_eq!(
+        Ast::from_str(
+            "{% macro hello(name: &str, f: fn(u8) -> u8, count: Option<usize> = None) %}\
+             {% endmacro %}",
+            &syntax
+        )
+        .unwrap()
+        .nodes,
+        vec![Node::Macro(Macro {
+            ws1: Ws(None, None),
+            name: "hello",
+            args: vec![
+                MacroArg {
+                    name: "name",
+                    ty: Some("&str"),
+                    default: None,
+                },
+                MacroArg {
+                    name: "f",
+                    ty: Some("fn(u8) -> u8"),
+                    default: None,
+                },
+                MacroArg {
+                    name: "count",
+                    ty: Some("Option<usize>"),
+                    default: Some(Expr::Path(vec!["None"])),
+                },
+            ],
+            nodes: vec![],
+            ws2: Ws(None, None),
+        })],
+    );
+    assert!(Ast::from_str("{% macro hello(name: ) %}{% endmacro %}", &syntax).is_err());
+
+    let ast = Ast::from_str(
+        "{% macro hello(name: &'a str, items: Vec<(u32, &'a str)>) %}{% endmacro %}",
+        &syntax,
+    )
+    .unwrap();
+    let Node::Macro(m) = &ast.nodes[0] else {
+        panic!("expected a macro");
+    };
+    assert_eq!(
+        m.args.iter().map(|arg| arg.ty).collect::<Vec<_>>(),
+        vec![Some("&'a str"), Some("Vec<(u32, &'a str)>")],
+    );
+}
+
 #[test]
 fn fuzzed_unicode_slice() {
     let d = "{eeuuu{b&{!!&{!!11{{
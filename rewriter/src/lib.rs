@@ -3,53 +3,528 @@
 
 mod parser;
 mod rewriter;
+mod source_map;
 
+use ::parser::Syntax;
 use parser::Ast;
-use rewriter::normalize;
+pub use rewriter::normalize;
 use rewriter::Rewriter;
+pub use source_map::SourceMap;
+use std::fmt;
 use std::path::Path;
 
-pub fn transform_path<P: AsRef<Path>>(path: P) -> String {
+/// An error found while rewriting a JSX template, located in its source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+    message: String,
+    offset: usize,
+    row: usize,
+    column: usize,
+}
+
+impl Error {
+    pub(crate) fn new(message: impl Into<String>, source: &str, offset: usize) -> Self {
+        let (row, column) = position(source, offset);
+        Self {
+            message: message.into(),
+            offset,
+            row,
+            column,
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Byte offset of the error in the template source.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Row of the error, starting at 1.
+    pub fn row(&self) -> usize {
+        self.row
+    }
+
+    /// Column of the error in characters, starting at 0.
+    pub fn column(&self) -> usize {
+        self.column
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.message.fmt(f)
+    }
+}
+
+/// Settings of the rewriter.
+pub struct Options<'a> {
+    pub syntax: &'a Syntax<'a>,
+    pub naming: Naming,
+    /// Finds the template of a component from its path without extension, like
+    /// `forms/text_input`. Components that are not found are imported with `extension`.
+    pub resolve: &'a dyn Fn(&str) -> Result<Option<String>, String>,
+    /// Rewrites the template where it is, without wrapping it in a macro, like a template
+    /// pulled in by `{% include %}`.
+    pub include: bool,
+    /// Extension of the component templates, usually the one of the rewritten template.
+    pub extension: &'a str,
+}
+
+impl<'a> Options<'a> {
+    pub fn new(syntax: &'a Syntax<'a>) -> Self {
+        Self {
+            syntax,
+            naming: Naming::default(),
+            resolve: &unresolved,
+            include: false,
+            extension: "html",
+        }
+    }
+}
+
+fn unresolved(_: &str) -> Result<Option<String>, String> {
+    Ok(None)
+}
+
+/// How component names map to template file names. Whatever the convention, the macro
+/// defined by a template is its snake_case file stem: `<Forms.TextInput />` calls `text_input`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Naming {
+    /// `<Forms.TextInput />` lives in `forms/text_input.html`.
+    #[default]
+    SnakeCase,
+    /// `<Forms.TextInput />` lives in `forms/text-input.html`.
+    KebabCase,
+    /// `<Forms.TextInput />` lives in `Forms/TextInput.html`.
+    PascalCase,
+}
+
+/// Returns the row (1-based) and column (0-based, in characters) of `offset` in `source`.
+pub(crate) fn position(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset];
+    let line_start = before.rfind('\n').map_or(0, |pos| pos + 1);
+
+    (
+        before.matches('\n').count() + 1,
+        before[line_start..].chars().count(),
+    )
+}
+
+/// Generates the source of a template that calls the component at `path`, passing each
+/// of `props` by name from the context of the template.
+pub fn transform_path<P: AsRef<Path>>(path: P, props: &[&str], syntax: &Syntax<'_>) -> String {
     let macro_name = normalize(&path);
     let macro_path = path.as_ref().display();
+    let Syntax {
+        block_start,
+        block_end,
+        ..
+    } = syntax;
 
     format!(
-        "\
-        {{%- import \"{macro_path}\" as {macro_name}_scope -%}}\n\
-        {{% call {macro_name}_scope::{macro_name}() %}}{{% endcall %}}\n"
+        "{block_start}- import \"{macro_path}\" as {macro_name}_scope -{block_end}\n{}",
+        macro_call(&format!("{macro_name}_scope::{macro_name}"), props, syntax)
+    )
+}
+
+/// Generates the source of a template that extends the template at `path`, for templates
+/// using inheritance, which [`rewrite_source`] does not wrap in a macro.
+pub fn transform_extends<P: AsRef<Path>>(path: P, syntax: &Syntax<'_>) -> String {
+    let Syntax {
+        block_start,
+        block_end,
+        ..
+    } = syntax;
+
+    format!(
+        "{block_start} extends \"{}\" {block_end}\n",
+        path.as_ref().display()
+    )
+}
+
+/// Generates the call of the macro that [`rewrite_source`] defines for `path`, to be
+/// appended to its output, passing each of `props` by name like [`transform_path`].
+pub fn transform_source<P: AsRef<Path>>(path: P, props: &[&str], syntax: &Syntax<'_>) -> String {
+    macro_call(&normalize(path), props, syntax)
+}
+
+fn macro_call(target: &str, props: &[&str], syntax: &Syntax<'_>) -> String {
+    let macro_args = props
+        .iter()
+        .map(|prop| format!("{prop}={prop}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let Syntax {
+        block_start,
+        block_end,
+        ..
+    } = syntax;
+
+    format!(
+        "{block_start} call {target}({macro_args}) {block_end}\
+         {block_start} endcall {block_end}\n"
     )
 }
 
-pub fn rewrite_source<P: AsRef<Path>>(path: P, source: String) -> String {
+/// Rewrites a JSX template into askama macros, along with a map of the rewritten
+/// source back to `source`.
+pub fn rewrite_source<P: AsRef<Path>>(
+    path: P,
+    source: &str,
+    options: &Options<'_>,
+) -> Result<(String, SourceMap), Error> {
     let macro_name = normalize(path);
 
-    let parsed = match Ast::from_str(&source) {
-        Ok(parsed) => parsed,
-        Err(_) => return source,
-    };
+    let parsed = Ast::from_str(source, options.syntax)?;
 
-    Rewriter::new(&parsed.nodes)
-        .build(&macro_name)
-        .unwrap_or(source)
+    Rewriter::new(source, &parsed.nodes, options).build(&macro_name)
 }
 
 #[test]
 fn test_transform_path() {
     assert_eq!(
-        transform_path("templates/hello_world.html"),
+        transform_path("templates/hello_world.html", &[], &Syntax::default()),
         "\
         {%- import \"templates/hello_world.html\" as hello_world_scope -%}\n\
         {% call hello_world_scope::hello_world() %}{% endcall %}\n"
     );
+
+    assert_eq!(
+        transform_path("index.html", &["title", "user"], &Syntax::default()),
+        "\
+        {%- import \"index.html\" as index_scope -%}\n\
+        {% call index_scope::index(title=title, user=user) %}{% endcall %}\n"
+    );
+
+    assert_eq!(
+        transform_source("HelloPage.html", &["name"], &Syntax::default()),
+        "{% call hello_page(name=name) %}{% endcall %}\n"
+    );
 }
 
 #[test]
 fn test_rewrite_source() {
     assert_eq!(
-        rewrite_source("index", "<Hello name />".into()),
+        rewrite_source("index", "<Hello name />", &Options::new(&Syntax::default()))
+            .unwrap()
+            .0,
         "\
         {%- import \"hello.html\" as hello_scope -%}\n\
         {% macro index() %}\n\
         {% call hello_scope::hello(name) %}{% endcall %}{% endmacro index %}\n"
     );
 }
+
+#[test]
+fn test_rewrite_source_attrs() {
+    assert_eq!(
+        rewrite_source("index", "<Hello\n  title=\"hello world\"\n  name/>", &Options::new(&Syntax::default())).unwrap().0,
+        "\
+        {%- import \"hello.html\" as hello_scope -%}\n\
+        {% macro index() %}\n\
+        {% call hello_scope::hello(title=\"hello world\", name) %}{% endcall %}{% endmacro index %}\n"
+    );
+
+    assert_eq!(
+        rewrite_source(
+            "index",
+            "<Hello name={ user.name|upper } />",
+            &Options::new(&Syntax::default())
+        )
+        .unwrap()
+        .0,
+        "\
+        {%- import \"hello.html\" as hello_scope -%}\n\
+        {% macro index() %}\n\
+        {% call hello_scope::hello(name=user.name|upper) %}{% endcall %}{% endmacro index %}\n"
+    );
+}
+
+#[test]
+fn test_rewrite_source_opaque() {
+    assert_eq!(
+        rewrite_source(
+            "index",
+            "{# <Todo> #}<script>Array<User></script><Hello />{{ \"<B>\" }}",
+            &Options::new(&Syntax::default())
+        )
+        .unwrap()
+        .0,
+        "\
+        {%- import \"hello.html\" as hello_scope -%}\n\
+        {% macro index() %}\n\
+        {# <Todo> #}<script>Array<User></script>\
+        {% call hello_scope::hello() %}{% endcall %}\
+        {{ \"<B>\" }}{% endmacro index %}\n"
+    );
+}
+
+#[test]
+fn test_rewrite_source_syntax() {
+    let syntax = Syntax {
+        block_start: "<%",
+        block_end: "%>",
+        expr_start: "<<",
+        expr_end: ">>",
+        comment_start: "<#",
+        comment_end: "#>",
+    };
+
+    assert_eq!(
+        rewrite_source(
+            "index",
+            "<#def name #><Hello name /><# <Todo> #>",
+            &Options::new(&syntax)
+        )
+        .unwrap()
+        .0,
+        "\
+        <%- import \"hello.html\" as hello_scope -%>\n\
+        <% macro index(name) %>\n\
+        <% call hello_scope::hello(name) %><% endcall %><# <Todo> #><% endmacro index %>\n"
+    );
+
+    assert_eq!(
+        transform_path("hello.html", &[], &syntax),
+        "\
+        <%- import \"hello.html\" as hello_scope -%>\n\
+        <% call hello_scope::hello() %><% endcall %>\n"
+    );
+
+    assert_eq!(
+        rewrite_source(
+            "index",
+            "<#def name #><#def title #>",
+            &Options::new(&syntax)
+        )
+        .unwrap_err()
+        .to_string(),
+        "multiple <#def #> blocks found, the second one at row 1, column 13"
+    );
+}
+
+#[test]
+fn test_rewrite_source_error() {
+    let err = rewrite_source(
+        "index",
+        "<Card>\n  <Hello />",
+        &Options::new(&Syntax::default()),
+    )
+    .unwrap_err();
+    assert_eq!(err.message(), "unclosed tag <Card> at row 1, column 0");
+    assert_eq!((err.offset(), err.row(), err.column()), (0, 1, 0));
+
+    let err = rewrite_source(
+        "index",
+        "<Hello />\n<Hello name=\"world />",
+        &Options::new(&Syntax::default()),
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "unterminated string in attribute value\n\
+         problems parsing JSX source at row 2, column 12 near:\n\"\\\"world />\""
+    );
+    assert_eq!((err.offset(), err.row(), err.column()), (22, 2, 12));
+}
+
+#[test]
+fn test_rewrite_source_map() {
+    let source = "<Hello name />\nHi {{ name }}";
+    let (rewritten, map) =
+        rewrite_source("index", source, &Options::new(&Syntax::default())).unwrap();
+
+    let offset = rewritten.find("Hi").unwrap();
+    assert_eq!(map.original_offset(offset), source.find("Hi").unwrap());
+
+    let offset = rewritten.find("{% call").unwrap();
+    assert_eq!(
+        map.original_offset(offset + 5),
+        source.find("Hello").unwrap()
+    );
+
+    let offset = rewritten.find("{%- import").unwrap();
+    assert_eq!(map.original_offset(offset), source.find("Hello").unwrap());
+
+    let offset = rewritten.find("{% endmacro").unwrap();
+    assert_eq!(map.original_offset(offset), source.len());
+}
+
+#[test]
+fn test_rewrite_source_resolve() {
+    let syntax = Syntax::default();
+    let resolve =
+        |path: &str| Ok((path == "ui/button").then(|| "components/ui/button.jinja".into()));
+    let options = Options {
+        syntax: &syntax,
+        naming: Naming::SnakeCase,
+        resolve: &resolve,
+        include: false,
+        extension: "html",
+    };
+
+    assert_eq!(
+        rewrite_source("index", "<Ui.Button /><Hello />", &options)
+            .unwrap()
+            .0,
+        "\
+        {%- import \"components/ui/button.jinja\" as ui_button_scope -%}\n\
+        {%- import \"hello.html\" as hello_scope -%}\n\
+        {% macro index() %}\n\
+        {% call ui_button_scope::button() %}{% endcall %}\
+        {% call hello_scope::hello() %}{% endcall %}{% endmacro index %}\n"
+    );
+
+    assert_eq!(
+        rewrite_source(
+            "index",
+            "{% macro button() %}{% endmacro %}\n<Ui.Button /><Button />",
+            &options
+        )
+        .unwrap()
+        .0,
+        "\
+        {%- import \"components/ui/button.jinja\" as ui_button_scope -%}\n\
+        {% macro button() %}{% endmacro %}\
+        {% macro index() %}\n\
+        \n{% call ui_button_scope::button() %}{% endcall %}\
+        {% call button() %}{% endcall %}{% endmacro index %}\n"
+    );
+
+    let resolve = |path: &str| Ok((path == "button").then(|| "button.html".into()));
+    let options = Options {
+        resolve: &resolve,
+        ..options
+    };
+    assert_eq!(
+        rewrite_source(
+            "index",
+            "{% macro button() %}{% endmacro %}\n<Button />",
+            &options
+        )
+        .unwrap_err()
+        .to_string(),
+        "<Button> at row 2, column 0 calls macro `button` defined at row 1, column 0, which \
+         has the name of component file \"button.html\""
+    );
+}
+
+#[test]
+fn test_rewrite_source_naming() {
+    let syntax = Syntax::default();
+    let mut options = Options::new(&syntax);
+
+    options.naming = Naming::KebabCase;
+    assert_eq!(
+        rewrite_source("index", "<Forms.TextInput />", &options)
+            .unwrap()
+            .0,
+        "\
+        {%- import \"forms/text-input.html\" as forms_text_input_scope -%}\n\
+        {% macro index() %}\n\
+        {% call forms_text_input_scope::text_input() %}{% endcall %}{% endmacro index %}\n"
+    );
+
+    options.naming = Naming::PascalCase;
+    assert_eq!(
+        rewrite_source("index", "<Forms.TextInput />", &options)
+            .unwrap()
+            .0,
+        "\
+        {%- import \"Forms/TextInput.html\" as forms_text_input_scope -%}\n\
+        {% macro index() %}\n\
+        {% call forms_text_input_scope::text_input() %}{% endcall %}{% endmacro index %}\n"
+    );
+
+    assert_eq!(
+        rewrite_source("index", "<HTMLInput />\n<HtmlInput />", &options)
+            .unwrap_err()
+            .to_string(),
+        "<HtmlInput> at row 2, column 0 and <HTMLInput> at row 1, column 0 \
+         both import as scope `html_input_scope`"
+    );
+
+    let resolve = |_: &str| Err("found both a and b".to_owned());
+    options.resolve = &resolve;
+    assert_eq!(
+        rewrite_source("index", "<Hello />", &options)
+            .unwrap_err()
+            .to_string(),
+        "unable to resolve <Hello> at row 1, column 0: found both a and b"
+    );
+}
+
+#[test]
+fn test_rewrite_source_components() {
+    let syntax = Syntax::default();
+    let resolve = |path: &str| Ok((path == "badges").then(|| "badges.html".into()));
+    let options = Options {
+        syntax: &syntax,
+        naming: Naming::SnakeCase,
+        resolve: &resolve,
+        include: false,
+        extension: "html",
+    };
+
+    assert_eq!(
+        rewrite_source(
+            "index",
+            "<Badges.Badge text=\"new\" /><Badges.Pill /><Badges />",
+            &options
+        )
+        .unwrap()
+        .0,
+        "\
+        {%- import \"badges.html\" as badges_scope -%}\n\
+        {% macro index() %}\n\
+        {% call badges_scope::badge(text=\"new\") %}{% endcall %}\
+        {% call badges_scope::pill() %}{% endcall %}\
+        {% call badges_scope::badges() %}{% endcall %}{% endmacro index %}\n"
+    );
+}
+
+#[test]
+fn test_rewrite_source_include() {
+    let syntax = Syntax::default();
+    let mut options = Options::new(&syntax);
+    options.include = true;
+
+    assert_eq!(
+        rewrite_source("nav", "<nav><NavItem href=\"/\" /></nav>", &options)
+            .unwrap()
+            .0,
+        "\
+        {%- import \"nav_item.html\" as nav_item_scope -%}\n\
+        <nav>{% call nav_item_scope::nav_item(href=\"/\") %}{% endcall %}</nav>"
+    );
+
+    assert_eq!(
+        rewrite_source("nav", "{#def items #}<nav></nav>", &options)
+            .unwrap_err()
+            .to_string(),
+        "{#def #} at row 1, column 0 cannot be used in an included template, its props are \
+         the variables of the template including it"
+    );
+}
+
+#[test]
+fn test_rewrite_source_extension() {
+    let syntax = Syntax::default();
+    let mut options = Options::new(&syntax);
+    options.extension = "txt";
+
+    assert_eq!(
+        rewrite_source("welcome", "<Signature />", &options)
+            .unwrap()
+            .0,
+        "\
+        {%- import \"signature.txt\" as signature_scope -%}\n\
+        {% macro welcome() %}\n\
+        {% call signature_scope::signature() %}{% endcall %}{% endmacro welcome %}\n"
+    );
+}
@@ -1,35 +1,86 @@
+use crate::position;
+use crate::Error;
 use nom::branch::alt;
+use nom::bytes::complete::escaped;
+use nom::bytes::complete::is_not;
 use nom::bytes::complete::tag;
-use nom::bytes::complete::take_till;
+use nom::bytes::complete::take;
 use nom::character::complete::alpha1;
+use nom::character::complete::alphanumeric1;
 use nom::character::complete::anychar;
 use nom::character::complete::char;
-use nom::character::complete::space0;
-use nom::character::complete::space1;
+use nom::character::complete::multispace0;
+use nom::character::complete::multispace1;
+use nom::character::complete::one_of;
 use nom::combinator::complete;
 use nom::combinator::cut;
 use nom::combinator::eof;
 use nom::combinator::map;
+use nom::combinator::map_opt;
 use nom::combinator::not;
 use nom::combinator::opt;
+use nom::combinator::peek;
 use nom::combinator::recognize;
 use nom::combinator::verify;
 use nom::error::ErrorKind;
 use nom::error_position;
 use nom::multi::many0;
-use nom::sequence::delimited;
+use nom::multi::many0_count;
+use nom::multi::many1_count;
+use nom::multi::separated_list0;
+use nom::sequence::pair;
+use nom::sequence::preceded;
 use nom::sequence::terminated;
 use nom::sequence::tuple;
+use parser::Syntax;
+use std::borrow::Cow;
 
 const JSX_BLOCK_START: &str = "<";
 const JSX_BLOCK_END: &str = ">";
 const JSX_CLOSE_START: &str = "</";
-const MACRO_DEF_START: &str = "{#def";
-const MACRO_DEF_END: &str = "#}";
+const JSX_SELF_CLOSE: &str = "/";
+const JSX_EXPR_START: char = '{';
+const JSX_EXPR_END: char = '}';
+pub(crate) const MACRO_DEF_KEYWORD: &str = "def";
+pub(crate) const COMPONENT_KEYWORD: &str = "component";
+const COMPONENT_END_KEYWORD: &str = "/component";
+const RAW_TEXT_ELEMENTS: [&str; 3] = ["script", "style", "textarea"];
+const INHERITANCE_KEYWORDS: [&str; 2] = ["extends", "block"];
+const IMPORT_KEYWORD: &str = "import";
+const MACRO_KEYWORD: &str = "macro";
+const MACRO_END_KEYWORD: &str = "endmacro";
 
-type ParseResult<'a, T = &'a str> = nom::IResult<&'a str, T>;
+type ParseResult<'a, T = &'a str> = Result<(&'a str, T), nom::Err<ErrorContext<'a>>>;
 
-pub(crate) struct ParseError;
+/// Keeps the position and an optional message of a `nom` error,
+/// so that malformed tags can be reported where they occur.
+#[derive(Debug, PartialEq)]
+struct ErrorContext<'a> {
+    input: &'a str,
+    message: Option<Cow<'static, str>>,
+}
+
+impl<'a> nom::error::ParseError<&'a str> for ErrorContext<'a> {
+    fn from_error_kind(input: &'a str, _code: ErrorKind) -> Self {
+        Self {
+            input,
+            message: None,
+        }
+    }
+
+    fn append(_: &'a str, _: ErrorKind, other: Self) -> Self {
+        other
+    }
+}
+
+impl<'a> ErrorContext<'a> {
+    fn fail<T>(input: &'a str, message: impl Into<Cow<'static, str>>) -> ParseResult<'a, T> {
+        Err(nom::Err::Failure(Self {
+            input,
+            message: Some(message.into()),
+        }))
+    }
+}
 
 #[derive(Debug)]
 pub(crate) struct Ast<'a> {
@@ -37,13 +88,39 @@ pub(crate) struct Ast<'a> {
 }
 
 impl<'a> Ast<'a> {
-    pub(crate) fn from_str(src: &'a str) -> Result<Self, ParseError> {
-        let parse = |i: &'a str| Node::many(i);
+    pub(crate) fn from_str(src: &'a str, syntax: &Syntax<'_>) -> Result<Self, Error> {
+        let parse = |i: &'a str| Node::many(i, syntax);
 
-        match terminated(parse, cut(eof))(src) {
-            Ok(("", nodes)) => Ok(Self { nodes }),
-            Ok(_) | Err(_) => Err(ParseError),
-        }
+        let (input, message) = match terminated(parse, cut(eof))(src) {
+            Ok(("", nodes)) => return Ok(Self { nodes }),
+            Ok(_) => unreachable!("eof() is not eof?"),
+            Err(
+                nom::Err::Error(ErrorContext { input, message })
+                | nom::Err::Failure(ErrorContext { input, message }),
+            ) => (input, message),
+            Err(nom::Err::Incomplete(_)) => {
+                return Err(Error::new("parsing incomplete", src, src.len()))
+            }
+        };
+
+        let offset = src.len() - input.len();
+        let source_after = &src[offset..];
+
+        let source_after = match source_after.char_indices().enumerate().take(41).last() {
+            Some((40, (i, _))) => format!("{:?}...", &source_after[..i]),
+            _ => format!("{source_after:?}"),
+        };
+
+        let (row, column) = position(src, offset);
+
+        Err(Error::new(
+            format!(
+                "{}problems parsing JSX source at row {row}, column {column} near:\n{source_after}",
+                message.map(|m| format!("{m}\n")).unwrap_or_default(),
+            ),
+            src,
+            offset,
+        ))
     }
 }
 
@@ -53,25 +130,34 @@ pub(crate) enum Node<'a> {
     JsxBlock(JsxBlock<'a>),
     JsxClose(JsxClose<'a>),
     MacroDef(MacroDef<'a>),
+    ComponentDef(ComponentDef<'a>),
+    ComponentEnd(ComponentEnd<'a>),
+    ImportTag(ImportTag<'a>),
+    MacroTag(MacroTag<'a>),
+    MacroEndTag(MacroEndTag<'a>),
 }
 
 impl<'a> Node<'a> {
-    fn many(i: &'a str) -> ParseResult<'a, Vec<Self>> {
+    fn many(i: &'a str, s: &Syntax<'_>) -> ParseResult<'a, Vec<Self>> {
         complete(many0(alt((
-            map(Lit::parse, Self::Lit),
-            map(MacroDef::parse, Self::MacroDef),
+            map(|i| Lit::parse(i, s), Self::Lit),
+            map(|i| MacroDef::parse(i, s), Self::MacroDef),
+            map(|i| ComponentDef::parse(i, s), Self::ComponentDef),
+            map(|i| ComponentEnd::parse(i, s), Self::ComponentEnd),
+            map(|i| ImportTag::parse(i, s), Self::ImportTag),
+            map(|i| MacroTag::parse(i, s), Self::MacroTag),
+            map(|i| MacroEndTag::parse(i, s), Self::MacroEndTag),
             Self::parse,
         ))))(i)
     }
 
     fn parse(i: &'a str) -> ParseResult<'a, Self> {
-        delimited(
+        preceded(
             |i| tag(JSX_BLOCK_START)(i),
             alt((
                 map(JsxBlock::parse, Self::JsxBlock),
                 map(JsxClose::parse, Self::JsxClose),
             )),
-            cut(|i| tag(JSX_BLOCK_END)(i)),
         )(i)
     }
 }
@@ -82,102 +168,747 @@ pub(crate) struct Lit<'a> {
 }
 
 impl<'a> Lit<'a> {
-    fn parse(i: &'a str) -> ParseResult<'a, Self> {
+    fn parse(i: &'a str, s: &Syntax<'_>) -> ParseResult<'a, Self> {
         let p_start = alt((
-            tuple((tag(JSX_BLOCK_START), verify(alpha1, is_uppercase_first))),
-            tuple((tag(JSX_CLOSE_START), verify(alpha1, is_uppercase_first))),
-            tuple((tag(MACRO_DEF_START), verify(space0, |_: &str| true))),
+            recognize(tuple((tag(JSX_BLOCK_START), component_name))),
+            recognize(tuple((tag(JSX_CLOSE_START), component_name))),
+            recognize(tuple((tag(JSX_BLOCK_START), tag(JSX_BLOCK_END)))),
+            recognize(tuple((tag(JSX_CLOSE_START), tag(JSX_BLOCK_END)))),
+            recognize(tuple((|i| macro_def_start(i, s), multispace1))),
+            recognize(tuple((|i| component_start(i, s), multispace1))),
+            recognize(|i| ComponentEnd::parse(i, s)),
+            recognize(|i| ImportTag::parse(i, s)),
+            recognize(|i| MacroTag::parse(i, s)),
+            recognize(|i| MacroEndTag::parse(i, s)),
         ));
+        let mut p_next = preceded(not(p_start), alt((|i| opaque(i, s), recognize(anychar))));
 
         let (i, _) = not(eof)(i)?;
-        let (i, content) = opt(recognize(skip_till(p_start)))(i)?;
+        let mut rest = i;
+        while let Ok((next, _)) = p_next(rest) {
+            rest = next;
+        }
+
+        match &i[..i.len() - rest.len()] {
+            "" => Err(nom::Err::Error(error_position!(i, ErrorKind::TakeUntil))),
+            content => Ok((rest, Self { val: content })),
+        }
+    }
+}
+
+/// Recognizes a region whose content must never be rewritten: askama raw blocks,
+/// comments, expressions and block tags, and the raw text of HTML elements like
+/// `<script>`. An unterminated region extends to the end of the input.
+fn opaque<'a>(i: &'a str, s: &Syntax<'_>) -> ParseResult<'a> {
+    alt((
+        |i| raw_block(i, s),
+        |i| comment(i, s),
+        |i| tag_region(i, s.expr_start, s.expr_end),
+        |i| tag_region(i, s.block_start, s.block_end),
+        raw_text_element,
+    ))(i)
+}
+
+/// Tells whether `lit` has an askama `extends` or `block` tag, outside of raw blocks,
+/// comments and expressions.
+pub(crate) fn has_inheritance_tags(lit: &str, s: &Syntax<'_>) -> bool {
+    let mut i = lit;
+    while !i.is_empty() {
+        if let Ok((rest, _)) = raw_block(i, s) {
+            i = rest;
+        } else if let Ok((rest, tag)) = tag_region(i, s.block_start, s.block_end) {
+            let keyword = tag[s.block_start.len()..]
+                .trim_start_matches(['-', '+', '~'])
+                .trim_start();
+            if matches!(identifier(keyword), Ok((_, keyword)) if INHERITANCE_KEYWORDS.contains(&keyword))
+            {
+                return true;
+            }
+            i = rest;
+        } else if let Ok((rest, _)) = opaque(i, s) {
+            i = rest;
+        } else {
+            let mut chars = i.chars();
+            chars.next();
+            i = chars.as_str();
+        }
+    }
+    false
+}
+
+fn raw_block<'a>(i: &'a str, s: &Syntax<'_>) -> ParseResult<'a> {
+    let block = |keyword| {
+        recognize(tuple((
+            tag(s.block_start),
+            opt(one_of("-+~")),
+            multispace0,
+            tag(keyword),
+            multispace0,
+            opt(one_of("-+~")),
+            tag(s.block_end),
+        )))
+    };
 
-        match content {
-            Some("") => Err(nom::Err::Error(error_position!(i, ErrorKind::TakeUntil))),
-            Some(content) => Ok((i, Self { val: content })),
-            None => Ok(("", Self { val: i })),
+    recognize(tuple((
+        block("raw"),
+        skip_till(block("endraw")),
+        block("endraw"),
+    )))(i)
+}
+
+/// Comments can be nested, like in askama.
+fn comment<'a>(i: &'a str, s: &Syntax<'_>) -> ParseResult<'a> {
+    let (mut rest, _) = tag(s.comment_start)(i)?;
+    let mut level = 0;
+    loop {
+        let Some(end) = rest.find(s.comment_end) else {
+            return Ok(("", i));
+        };
+        match rest.find(s.comment_start) {
+            Some(start) if start < end => {
+                level += 1;
+                rest = &rest[start + s.comment_start.len()..];
+            }
+            _ => {
+                rest = &rest[end + s.comment_end.len()..];
+                if level == 0 {
+                    return Ok((rest, &i[..i.len() - rest.len()]));
+                }
+                level -= 1;
+            }
+        }
+    }
+}
+
+/// Skips an askama tag up to `end`, ignoring delimiters inside string and char literals.
+fn tag_region<'a>(i: &'a str, start: &str, end: &str) -> ParseResult<'a> {
+    let (rest, _) = tag(start)(i)?;
+
+    let mut escaped = false;
+    let mut in_str = false;
+    let mut skip_to = 0;
+    for (pos, c) in rest.char_indices() {
+        match c {
+            _ if pos < skip_to => {}
+            _ if escaped => escaped = false,
+            '\\' if in_str => escaped = true,
+            '"' => in_str = !in_str,
+            '\'' if !in_str => skip_to = pos + char_lit_len(&rest[pos..]).unwrap_or(0),
+            _ if !in_str && rest[pos..].starts_with(end) => {
+                let pos = i.len() - rest.len() + pos + end.len();
+                return Ok((&i[pos..], &i[..pos]));
+            }
+            _ => {}
+        }
+    }
+    Ok(("", i))
+}
+
+/// Returns the length of the char literal at the start of `i`, like `'"'` or `'\''`.
+/// A `'` which does not start one is the start of a lifetime, like in `&'a str`.
+fn char_lit_len(i: &str) -> Option<usize> {
+    let mut chars = i.char_indices().skip(1);
+    match chars.next()? {
+        (_, '\\') => {
+            chars.next()?;
+            chars.find(|&(_, c)| c == '\'').map(|(pos, _)| pos + 1)
+        }
+        _ => match chars.next()? {
+            (pos, '\'') => Some(pos + 1),
+            _ => None,
+        },
+    }
+}
+
+/// Skips an HTML element whose content is raw text, up to its closing tag.
+fn raw_text_element(i: &str) -> ParseResult<'_> {
+    let (rest, name) = preceded(
+        tag(JSX_BLOCK_START),
+        terminated(identifier, peek(one_of(" \t\r\n/>"))),
+    )(i)?;
+    // HTML tag names are case-insensitive, but `<Textarea />` is a component.
+    if is_uppercase_first(name)
+        || !RAW_TEXT_ELEMENTS
+            .iter()
+            .any(|element| element.eq_ignore_ascii_case(name))
+    {
+        return Err(nom::Err::Error(error_position!(i, ErrorKind::Tag)));
+    }
+
+    let close = rest.match_indices(JSX_CLOSE_START).find(|(pos, _)| {
+        rest[pos + JSX_CLOSE_START.len()..]
+            .get(..name.len())
+            .is_some_and(|close| close.eq_ignore_ascii_case(name))
+    });
+    match close {
+        Some((pos, _)) => {
+            let pos = i.len() - rest.len() + pos + JSX_CLOSE_START.len() + name.len();
+            Ok((&i[pos..], &i[..pos]))
         }
+        None => Ok(("", i)),
     }
 }
 
 #[derive(Debug, PartialEq)]
 pub(crate) struct JsxBlock<'a> {
     pub(crate) name: &'a str,
-    pub(crate) args: Vec<&'a str>,
+    pub(crate) args: Vec<JsxAttr<'a>>,
+    pub(crate) spread: Option<&'a str>,
     pub(crate) self_closing: bool,
 }
 
 impl<'a> JsxBlock<'a> {
-    fn parse(i: &'a str) -> ParseResult<'a, Self> {
-        let mut p = tuple((
-            recognize(verify(alpha1, is_uppercase_first)),
-            opt(take_till(|c: char| c.to_string() == JSX_BLOCK_END)),
-        ));
+    pub(crate) fn is_fragment(&self) -> bool {
+        self.name.is_empty()
+    }
 
-        let (i, (name, args)) = p(i)?;
+    /// Splits a slot name like `Card:header` into its component and slot names.
+    pub(crate) fn slot(&self) -> Option<(&'a str, &'a str)> {
+        self.name.split_once(':')
+    }
+
+    fn parse(input: &'a str) -> ParseResult<'a, Self> {
+        let (mut i, name) = alt((component_name, fragment))(input)?;
+        let mut args = Vec::new();
+        let mut spread = None;
+        let mut last_bare = None;
 
-        let args = args
-            .map(|s| s.trim())
-            .unwrap_or("")
-            .split_ascii_whitespace()
-            .filter(|s| !s.is_empty())
-            .collect::<Vec<_>>();
+        loop {
+            let (j, attr) = match preceded(multispace1, Attr::parse)(i) {
+                Ok(result) => result,
+                Err(nom::Err::Error(_)) => break,
+                Err(err) => return Err(err),
+            };
 
-        let self_closing = args.last().map(|s| s.ends_with('/')).unwrap_or(false);
+            // A bare value ends where the attribute does, unlike quoted and `{...}` values.
+            last_bare = match &attr {
+                Attr::Named(JsxAttr {
+                    name,
+                    value: Some(value),
+                }) if !value.starts_with('"') && j.as_ptr() == value[value.len()..].as_ptr() => {
+                    Some((*name, *value))
+                }
+                _ => None,
+            };
 
-        let args = args
-            .iter()
-            .filter(|s| !s.ends_with('/'))
-            .copied()
-            .collect::<Vec<&str>>();
+            match attr {
+                Attr::Named(arg) => args.push(arg),
+                Attr::Spread(_) if spread.is_some() => {
+                    return ErrorContext::fail(
+                        i.trim_start(),
+                        format!("only one spread attribute is allowed in <{name}> tag"),
+                    );
+                }
+                Attr::Spread(value) => spread = Some(value),
+            }
+
+            i = j;
+        }
+
+        let (i, _) = multispace0(i)?;
+        let (i, self_closing) = opt(tag(JSX_SELF_CLOSE))(i)?;
+        let end = i;
+
+        let (i, _) = match tag::<_, _, ErrorContext<'a>>(JSX_BLOCK_END)(i) {
+            Ok(result) => result,
+            Err(_) if i.is_empty() => {
+                return ErrorContext::fail(i, format!("unterminated <{name}> tag"));
+            }
+            Err(_) if self_closing.is_some() => {
+                return ErrorContext::fail(i, format!("expected `>` after `/` in <{name}> tag"));
+            }
+            Err(_) => {
+                return ErrorContext::fail(
+                    i,
+                    format!("expected attribute, `/>` or `>` in <{name}> tag"),
+                );
+            }
+        };
+
+        // `<Hello when=a > b />` ends the tag at the `>` of the bare value: the tag goes on
+        // up to a `/>` before any child tag or line break.
+        if let (Some((attr, value)), None) = (last_bare, self_closing) {
+            let line = i.split(['<', '\n']).next().unwrap_or_default();
+            if let Some(pos) = line.find(&format!("{JSX_SELF_CLOSE}{JSX_BLOCK_END}")) {
+                let offset = |s: &str| s.as_ptr() as usize - input.as_ptr() as usize;
+                let expr = input[offset(value)..offset(i) + pos].trim();
+                return ErrorContext::fail(
+                    end,
+                    format!(
+                        "bare attribute values cannot contain `>`, \
+                         use `{attr}={{ {expr} }}` in <{name}> tag"
+                    ),
+                );
+            }
+        }
 
         Ok((
             i,
             Self {
                 name,
                 args,
-                self_closing,
+                spread,
+                self_closing: self_closing.is_some(),
             },
         ))
     }
 }
 
+enum Attr<'a> {
+    Named(JsxAttr<'a>),
+    Spread(&'a str),
+}
+
+impl<'a> Attr<'a> {
+    fn parse(i: &'a str) -> ParseResult<'a, Self> {
+        alt((
+            map(spread_value, Self::Spread),
+            map(JsxAttr::parse, Self::Named),
+        ))(i)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct JsxAttr<'a> {
+    pub(crate) name: &'a str,
+    pub(crate) value: Option<&'a str>,
+}
+
+impl<'a> JsxAttr<'a> {
+    fn parse(i: &'a str) -> ParseResult<'a, Self> {
+        let (i, name) = identifier(i)?;
+        let (i, value) = opt(preceded(
+            tuple((multispace0, char('='), multispace0)),
+            cut(Self::value),
+        ))(i)?;
+
+        Ok((i, Self { name, value }))
+    }
+
+    fn value(i: &'a str) -> ParseResult<'a> {
+        if i.starts_with(JSX_EXPR_START) {
+            return expr_value(i);
+        }
+
+        if i.starts_with('"') {
+            return match str_lit(i) {
+                Ok(result) => Ok(result),
+                Err(_) => ErrorContext::fail(i, "unterminated string in attribute value"),
+            };
+        }
+
+        match bare_value(i) {
+            Ok(result) => Ok(result),
+            Err(_) => ErrorContext::fail(i, "expected attribute value"),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub(crate) struct JsxClose<'a> {
     pub(crate) name: &'a str,
 }
 
 impl<'a> JsxClose<'a> {
+    pub(crate) fn is_fragment(&self) -> bool {
+        self.name.is_empty()
+    }
+
+    pub(crate) fn is_slot(&self) -> bool {
+        self.name.contains(':')
+    }
+
     fn parse(i: &'a str) -> ParseResult<'a, Self> {
-        let mut p = tuple((char('/'), recognize(verify(alpha1, is_uppercase_first))));
+        let mut p = tuple((char('/'), alt((component_name, fragment))));
 
         let (i, (_, name)) = p(i)?;
+        let (i, _) = multispace0(i)?;
 
-        Ok((i, Self { name }))
+        match tag::<_, _, ErrorContext<'a>>(JSX_BLOCK_END)(i) {
+            Ok((i, _)) => Ok((i, Self { name })),
+            Err(_) => ErrorContext::fail(i, format!("expected `>` in </{name}> tag")),
+        }
     }
 }
 
 #[derive(Debug, PartialEq)]
 pub(crate) struct MacroDef<'a> {
-    pub(crate) args: Vec<&'a str>,
+    pub(crate) start: &'a str,
+    pub(crate) args: Vec<MacroArg<'a>>,
 }
 
 impl<'a> MacroDef<'a> {
-    fn parse(i: &'a str) -> ParseResult<'a, Self> {
-        let mut p = tuple((
-            tag(MACRO_DEF_START),
-            space1,
-            recognize(alpha1),
-            space1,
-            tag(MACRO_DEF_END),
+    fn parse(i: &'a str, s: &Syntax<'_>) -> ParseResult<'a, Self> {
+        let separator = alt((
+            recognize(tuple((multispace0, char(','), multispace0))),
+            multispace1,
+        ));
+
+        let (i, (start, _)) = tuple((|i| macro_def_start(i, s), multispace1))(i)?;
+        let (i, args) = separated_list0(separator, |i| MacroArg::parse(i, s))(i)?;
+        let (i, _) = tuple((opt(pair(multispace0, char(','))), multispace0))(i)?;
+
+        match tag::<_, _, ErrorContext<'a>>(s.comment_end)(i) {
+            Ok((i, _)) => Ok((i, Self { start, args })),
+            Err(_) => ErrorContext::fail(
+                i,
+                format!(
+                    "expected identifier, `,` or `{}` in {}",
+                    s.comment_end,
+                    comment_tag(s, MACRO_DEF_KEYWORD),
+                ),
+            ),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct MacroArg<'a> {
+    pub(crate) name: &'a str,
+    pub(crate) ty: Option<&'a str>,
+    pub(crate) default: Option<&'a str>,
+}
+
+impl<'a> MacroArg<'a> {
+    fn parse(i: &'a str, s: &Syntax<'_>) -> ParseResult<'a, Self> {
+        let (i, name) = identifier(i)?;
+        let (i, ty) = opt(preceded(
+            tuple((multispace0, char(':'), multispace0)),
+            cut(|i| Self::ty(i, s)),
+        ))(i)?;
+        let (i, default) = opt(preceded(
+            tuple((multispace0, char('='), multispace0)),
+            cut(|i| Self::default(i, s)),
+        ))(i)?;
+
+        Ok((i, Self { name, ty, default }))
+    }
+
+    /// A type also ends at a line break or `#}` outside of brackets.
+    fn ty(i: &'a str, s: &Syntax<'_>) -> ParseResult<'a> {
+        let (rest, ty) = ::parser::node::MacroArg::split_ty(i, |rest| {
+            rest.starts_with('\n') || rest.starts_with(s.comment_end)
+        });
+        if ty.is_empty() {
+            return ErrorContext::fail(
+                i,
+                format!("expected type in {}", comment_tag(s, MACRO_DEF_KEYWORD)),
+            );
+        }
+        Ok((rest, ty))
+    }
+
+    fn default(i: &'a str, s: &Syntax<'_>) -> ParseResult<'a> {
+        if i.starts_with(JSX_EXPR_START) {
+            return expr_value(i);
+        }
+
+        if i.starts_with('"') {
+            return match str_lit(i) {
+                Ok(result) => Ok(result),
+                Err(_) => ErrorContext::fail(i, "unterminated string in default value"),
+            };
+        }
+
+        // A bare value also ends at a `)` closing the parameters of a `{#component}`.
+        let mut depth = 0usize;
+        let end = i
+            .char_indices()
+            .find(|&(pos, c)| match c {
+                '(' => {
+                    depth += 1;
+                    false
+                }
+                ')' if depth == 0 => true,
+                ')' => {
+                    depth -= 1;
+                    false
+                }
+                _ => {
+                    c.is_whitespace() || ",{}\"".contains(c) || i[pos..].starts_with(s.comment_end)
+                }
+            })
+            .map_or(i.len(), |(pos, _)| pos);
+        match end {
+            0 => ErrorContext::fail(
+                i,
+                format!(
+                    "expected default value in {}",
+                    comment_tag(s, MACRO_DEF_KEYWORD)
+                ),
+            ),
+            end => Ok((&i[end..], &i[..end])),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct ComponentDef<'a> {
+    pub(crate) start: &'a str,
+    pub(crate) name: &'a str,
+    pub(crate) args: Vec<MacroArg<'a>>,
+}
+
+impl<'a> ComponentDef<'a> {
+    /// Parses the opening of a `{#component Badge(text) #}` block. Parameters are written
+    /// like in `{#def}`, the parentheses may be omitted when there are none.
+    fn parse(i: &'a str, s: &Syntax<'_>) -> ParseResult<'a, Self> {
+        let separator = alt((
+            recognize(tuple((multispace0, char(','), multispace0))),
+            multispace1,
         ));
 
-        let (i, (_, _, args, _, _)) = p(i)?;
+        let (i, (start, _)) = tuple((|i| component_start(i, s), multispace1))(i)?;
+        let (i, name) = match verify(identifier, is_uppercase_first)(i) {
+            Ok(result) => result,
+            Err(_) => {
+                return ErrorContext::fail(
+                    i,
+                    format!(
+                        "expected component name in {}",
+                        comment_tag(s, COMPONENT_KEYWORD)
+                    ),
+                )
+            }
+        };
+
+        let (i, args) = match pair(multispace0::<_, ErrorContext<'a>>, char('('))(i) {
+            Ok((i, _)) => {
+                let (i, _) = multispace0(i)?;
+                let (i, args) = separated_list0(separator, |i| MacroArg::parse(i, s))(i)?;
+                let (i, _) = tuple((opt(pair(multispace0, char(','))), multispace0))(i)?;
+                match char::<_, ErrorContext<'a>>(')')(i) {
+                    Ok((i, _)) => (i, args),
+                    Err(_) => {
+                        return ErrorContext::fail(
+                            i,
+                            format!(
+                                "expected identifier, `,` or `)` in {}",
+                                comment_tag(s, &format!("{COMPONENT_KEYWORD} {name}")),
+                            ),
+                        );
+                    }
+                }
+            }
+            Err(_) => (i, Vec::new()),
+        };
+
+        let (i, _) = multispace0(i)?;
+        match tag::<_, _, ErrorContext<'a>>(s.comment_end)(i) {
+            Ok((i, _)) => Ok((i, Self { start, name, args })),
+            Err(_) => ErrorContext::fail(
+                i,
+                format!(
+                    "expected `(` or `{}` in {}",
+                    s.comment_end,
+                    comment_tag(s, &format!("{COMPONENT_KEYWORD} {name}")),
+                ),
+            ),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct ComponentEnd<'a> {
+    pub(crate) start: &'a str,
+}
+
+impl<'a> ComponentEnd<'a> {
+    /// Parses the end of a `{#component}` block, `{#/component #}`.
+    fn parse(i: &'a str, s: &Syntax<'_>) -> ParseResult<'a, Self> {
+        let (i, (start, ..)) = tuple((
+            recognize(pair(tag(s.comment_start), tag(COMPONENT_END_KEYWORD))),
+            multispace0,
+            tag(s.comment_end),
+        ))(i)?;
+
+        Ok((i, Self { start }))
+    }
+}
+
+/// A `{% import "forms.html" as forms %}` tag written in the template. Like macro
+/// definitions, it is moved to the top level, out of the generated macros.
+#[derive(Debug, PartialEq)]
+pub(crate) struct ImportTag<'a> {
+    pub(crate) val: &'a str,
+    pub(crate) scope: &'a str,
+}
+
+impl<'a> ImportTag<'a> {
+    fn parse(i: &'a str, s: &Syntax<'_>) -> ParseResult<'a, Self> {
+        let (_, (.., scope)) = tuple((
+            |i| block_tag_start(i, s, IMPORT_KEYWORD),
+            multispace1,
+            str_lit,
+            multispace1,
+            tag("as"),
+            multispace1,
+            identifier,
+        ))(i)?;
+        let (i, val) = tag_region(i, s.block_start, s.block_end)?;
+
+        Ok((i, Self { val, scope }))
+    }
+}
+
+/// The opening of a `{% macro name(args) %}` block written in the template.
+#[derive(Debug, PartialEq)]
+pub(crate) struct MacroTag<'a> {
+    pub(crate) val: &'a str,
+    pub(crate) name: &'a str,
+}
+
+impl<'a> MacroTag<'a> {
+    fn parse(i: &'a str, s: &Syntax<'_>) -> ParseResult<'a, Self> {
+        let (_, (.., name)) = tuple((
+            |i| block_tag_start(i, s, MACRO_KEYWORD),
+            multispace1,
+            identifier,
+        ))(i)?;
+        let (i, val) = tag_region(i, s.block_start, s.block_end)?;
+
+        Ok((i, Self { val, name }))
+    }
+}
+
+/// The end of a `{% macro %}` block, `{% endmacro %}`.
+#[derive(Debug, PartialEq)]
+pub(crate) struct MacroEndTag<'a> {
+    pub(crate) val: &'a str,
+}
+
+impl<'a> MacroEndTag<'a> {
+    fn parse(i: &'a str, s: &Syntax<'_>) -> ParseResult<'a, Self> {
+        let _ = block_tag_start(i, s, MACRO_END_KEYWORD)?;
+        let (i, val) = tag_region(i, s.block_start, s.block_end)?;
+
+        Ok((i, Self { val }))
+    }
+}
+
+/// Matches the opening of an askama block tag starting with `keyword`, like `{%- import`.
+fn block_tag_start<'a>(i: &'a str, s: &Syntax<'_>, keyword: &str) -> ParseResult<'a> {
+    recognize(tuple((
+        tag(s.block_start),
+        opt(one_of("-+~")),
+        multispace0,
+        tag(keyword),
+        not(alt((alphanumeric1, tag("_")))),
+    )))(i)
+}
+
+/// Matches the opening of a `{#component}` block.
+fn component_start<'a>(i: &'a str, s: &Syntax<'_>) -> ParseResult<'a> {
+    recognize(pair(tag(s.comment_start), tag(COMPONENT_KEYWORD)))(i)
+}
+
+/// Writes a comment tag like `{#def #}` in the syntax of the template, for error messages.
+pub(crate) fn comment_tag(s: &Syntax<'_>, content: &str) -> String {
+    format!("{}{content} {}", s.comment_start, s.comment_end)
+}
+
+/// Matches the opening of a `{#def}` block, a comment starting with the `def` keyword.
+fn macro_def_start<'a>(i: &'a str, s: &Syntax<'_>) -> ParseResult<'a> {
+    recognize(pair(tag(s.comment_start), tag(MACRO_DEF_KEYWORD)))(i)
+}
+
+/// A component name is a dot-separated path of capitalized identifiers, e.g. `Ui.Button`,
+/// or a macro of a scope imported in the template, e.g. `forms.Field`. It is optionally
+/// followed by the name of one of its slots, e.g. `Card:header`.
+fn component_name(i: &str) -> ParseResult<'_> {
+    let segment = || recognize(verify(identifier, is_uppercase_first));
 
-        let args = args.split_ascii_whitespace().collect();
+    recognize(tuple((
+        alt((
+            recognize(tuple((
+                verify(identifier, |s: &str| !is_uppercase_first(s)),
+                char('.'),
+                segment(),
+            ))),
+            recognize(pair(segment(), many0_count(pair(char('.'), segment())))),
+        )),
+        opt(pair(char(':'), identifier)),
+    )))(i)
+}
+
+/// Splits the name of a tag calling a macro of an imported scope, like `forms.Field`.
+pub(crate) fn scoped_name(name: &str) -> Option<(&str, &str)> {
+    name.split_once('.')
+        .filter(|(scope, _)| !is_uppercase_first(scope))
+}
+
+/// A fragment `<>...</>` is a component without a name.
+fn fragment(i: &str) -> ParseResult<'_> {
+    terminated(take(0usize), peek(tag(JSX_BLOCK_END)))(i)
+}
+
+fn identifier(i: &str) -> ParseResult<'_> {
+    recognize(pair(
+        alt((alpha1, tag("_"))),
+        many0_count(alt((alphanumeric1, tag("_")))),
+    ))(i)
+}
+
+/// A `{...}` attribute value holds an expression that is passed through as is.
+/// Braces are balanced, and those inside string or char literals are skipped.
+/// Returns the trimmed expression, without the enclosing braces.
+fn expr_value(i: &str) -> ParseResult<'_> {
+    let _ = char(JSX_EXPR_START)(i)?;
 
-        Ok((i, Self { args }))
+    let mut depth = 0;
+    let mut quote = None;
+    let mut escaped = false;
+
+    for (pos, c) in i.char_indices() {
+        match (quote, c) {
+            (Some(_), _) if escaped => escaped = false,
+            (Some(_), '\\') => escaped = true,
+            (Some(q), c) if q == c => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, JSX_EXPR_START) => depth += 1,
+            (None, JSX_EXPR_END) => {
+                depth -= 1;
+                if depth == 0 {
+                    let expr = i[1..pos].trim();
+                    if expr.is_empty() {
+                        return ErrorContext::fail(i, "expected expression in attribute value");
+                    }
+                    return Ok((&i[pos + 1..], expr));
+                }
+            }
+            (None, _) => {}
+        }
     }
+
+    ErrorContext::fail(i, "unterminated `{` in attribute value")
+}
+
+/// A spread attribute, either `{...expr}` or `..expr`.
+/// Returns the spread expression.
+fn spread_value(i: &str) -> ParseResult<'_> {
+    alt((
+        map_opt(expr_value, |expr| {
+            expr.strip_prefix("...").map(str::trim_start)
+        }),
+        preceded(terminated(tag(".."), not(char('.'))), bare_value),
+    ))(i)
+}
+
+/// An unquoted attribute value runs until whitespace or the end of the tag.
+fn bare_value(i: &str) -> ParseResult<'_> {
+    recognize(many1_count(alt((
+        is_not(" \t\r\n\"/>{"),
+        terminated(tag(JSX_SELF_CLOSE), not(tag(JSX_BLOCK_END))),
+    ))))(i)
+}
+
+fn str_lit(i: &str) -> ParseResult<'_> {
+    recognize(tuple((
+        char('"'),
+        opt(escaped(is_not("\\\""), '\\', anychar)),
+        char('"'),
+    )))(i)
 }
 
 fn is_uppercase_first(s: &str) -> bool {
@@ -213,107 +944,641 @@ fn skip_till<'a, O>(
 #[test]
 fn test_jsx_block() {
     assert_eq!(
-        JsxBlock::parse("Hello name rest=\"rest\" /"),
+        JsxBlock::parse("Hello name rest=\"rest\" />"),
         Ok((
             "",
             JsxBlock {
                 name: "Hello",
-                args: vec!["name", "rest=\"rest\""],
+                args: vec![
+                    JsxAttr {
+                        name: "name",
+                        value: None,
+                    },
+                    JsxAttr {
+                        name: "rest",
+                        value: Some("\"rest\""),
+                    },
+                ],
+                spread: None,
                 self_closing: true,
             }
         ))
     );
 
     assert_eq!(
-        JsxBlock::parse("Hello"),
+        JsxBlock::parse("Hello>"),
         Ok((
             "",
             JsxBlock {
                 name: "Hello",
                 args: vec![],
+                spread: None,
                 self_closing: false,
             }
         ))
     );
-}
 
-#[test]
-fn test_jsx_close() {
     assert_eq!(
-        JsxClose::parse("/Hello"),
-        Ok(("", JsxClose { name: "Hello" }))
+        JsxBlock::parse("Hello name/>"),
+        Ok((
+            "",
+            JsxBlock {
+                name: "Hello",
+                args: vec![JsxAttr {
+                    name: "name",
+                    value: None,
+                }],
+                spread: None,
+                self_closing: true,
+            }
+        ))
     );
-}
 
-#[test]
-fn test_macro_def() {
     assert_eq!(
-        MacroDef::parse("{#def name #}"),
-        Ok(("", MacroDef { args: vec!["name"] }))
+        JsxBlock::parse("Hello\n  title=\"hello \\\"world\\\" />\"\n  when=a/b\n/>"),
+        Ok((
+            "",
+            JsxBlock {
+                name: "Hello",
+                args: vec![
+                    JsxAttr {
+                        name: "title",
+                        value: Some("\"hello \\\"world\\\" />\""),
+                    },
+                    JsxAttr {
+                        name: "when",
+                        value: Some("a/b"),
+                    },
+                ],
+                spread: None,
+                self_closing: true,
+            }
+        ))
     );
 }
 
 #[test]
-fn test_lit() {
-    assert_eq!(Lit::parse("Test"), Ok(("", Lit { val: "Test" })));
-}
-
-#[test]
-fn test_node() {
-    assert_eq!(Node::many(""), Ok(("", vec![])));
-
+fn test_jsx_block_name() {
     assert_eq!(
-        Node::many("<Hello />"),
+        JsxBlock::parse("Card2 />"),
         Ok((
             "",
-            vec![Node::JsxBlock(JsxBlock {
-                name: "Hello",
+            JsxBlock {
+                name: "Card2",
                 args: vec![],
+                spread: None,
                 self_closing: true,
-            })]
+            }
         ))
     );
 
     assert_eq!(
-        Node::many("<Hello />\nTest"),
+        JsxBlock::parse("Forms.TextInput>"),
         Ok((
             "",
-            vec![
-                Node::JsxBlock(JsxBlock {
-                    name: "Hello",
-                    args: vec![],
-                    self_closing: true,
-                }),
-                Node::Lit(Lit { val: "\nTest" })
-            ]
+            JsxBlock {
+                name: "Forms.TextInput",
+                args: vec![],
+                spread: None,
+                self_closing: false,
+            }
         ))
     );
 
     assert_eq!(
-        Node::many("Test\n<Hello />"),
+        JsxClose::parse("/Forms.TextInput>"),
         Ok((
             "",
-            vec![
-                Node::Lit(Lit { val: "Test\n" }),
-                Node::JsxBlock(JsxBlock {
-                    name: "Hello",
-                    args: vec![],
-                    self_closing: true,
-                })
-            ],
+            JsxClose {
+                name: "Forms.TextInput"
+            }
         ))
     );
 
     assert_eq!(
-        Node::many("</Hello>"),
-        Ok(("", vec![Node::JsxClose(JsxClose { name: "Hello" })]))
+        JsxBlock::parse("Card:header>"),
+        Ok((
+            "",
+            JsxBlock {
+                name: "Card:header",
+                args: vec![],
+                spread: None,
+                self_closing: false,
+            }
+        ))
     );
 
     assert_eq!(
-        Node::many("</Hello>\nTest"),
+        JsxClose::parse("/Card:header>"),
         Ok((
             "",
-            vec![
+            JsxClose {
+                name: "Card:header"
+            }
+        ))
+    );
+
+    assert_eq!(
+        Node::many("<Forms.input />", &Syntax::default()),
+        Err(nom::Err::Failure(ErrorContext {
+            input: ".input />",
+            message: Some("expected attribute, `/>` or `>` in <Forms> tag".into()),
+        }))
+    );
+}
+
+#[test]
+fn test_jsx_fragment() {
+    assert_eq!(
+        Node::many("<>Test</>", &Syntax::default()),
+        Ok((
+            "",
+            vec![
+                Node::JsxBlock(JsxBlock {
+                    name: "",
+                    args: vec![],
+                    spread: None,
+                    self_closing: false,
+                }),
+                Node::Lit(Lit { val: "Test" }),
+                Node::JsxClose(JsxClose { name: "" }),
+            ]
+        ))
+    );
+
+    assert_eq!(
+        Node::many("< >", &Syntax::default()),
+        Ok(("", vec![Node::Lit(Lit { val: "< >" })]))
+    );
+}
+
+#[test]
+fn test_jsx_block_spread() {
+    assert_eq!(
+        JsxBlock::parse("Hello {...user} name />"),
+        Ok((
+            "",
+            JsxBlock {
+                name: "Hello",
+                args: vec![JsxAttr {
+                    name: "name",
+                    value: None,
+                }],
+                spread: Some("user"),
+                self_closing: true,
+            }
+        ))
+    );
+
+    assert_eq!(
+        JsxBlock::parse("Hello ..self/>"),
+        Ok((
+            "",
+            JsxBlock {
+                name: "Hello",
+                args: vec![],
+                spread: Some("self"),
+                self_closing: true,
+            }
+        ))
+    );
+
+    assert_eq!(
+        JsxBlock::parse("Hello ..self {...user} />"),
+        ErrorContext::fail(
+            "{...user} />",
+            "only one spread attribute is allowed in <Hello> tag"
+        ),
+    );
+
+    assert_eq!(
+        JsxBlock::parse("Hello { user } />"),
+        ErrorContext::fail(
+            "{ user } />",
+            "expected attribute, `/>` or `>` in <Hello> tag"
+        ),
+    );
+}
+
+#[test]
+fn test_jsx_block_expr() {
+    assert_eq!(
+        JsxBlock::parse("Hello count={items.len() + 1} label={ title|upper } />"),
+        Ok((
+            "",
+            JsxBlock {
+                name: "Hello",
+                args: vec![
+                    JsxAttr {
+                        name: "count",
+                        value: Some("items.len() + 1"),
+                    },
+                    JsxAttr {
+                        name: "label",
+                        value: Some("title|upper"),
+                    },
+                ],
+                spread: None,
+                self_closing: true,
+            }
+        ))
+    );
+
+    assert_eq!(
+        JsxBlock::parse("Hello when={a > b} text={(\"}\", '{', \"\\\"}\")} map={ {1: 2}[1] }>"),
+        Ok((
+            "",
+            JsxBlock {
+                name: "Hello",
+                args: vec![
+                    JsxAttr {
+                        name: "when",
+                        value: Some("a > b"),
+                    },
+                    JsxAttr {
+                        name: "text",
+                        value: Some("(\"}\", '{', \"\\\"}\")"),
+                    },
+                    JsxAttr {
+                        name: "map",
+                        value: Some("{1: 2}[1]"),
+                    },
+                ],
+                spread: None,
+                self_closing: false,
+            }
+        ))
+    );
+}
+
+#[test]
+fn test_jsx_block_error() {
+    assert_eq!(
+        JsxBlock::parse("Hello count={items.len( />"),
+        ErrorContext::fail("{items.len( />", "unterminated `{` in attribute value"),
+    );
+
+    assert_eq!(
+        JsxBlock::parse("Hello count={ } />"),
+        ErrorContext::fail("{ } />", "expected expression in attribute value"),
+    );
+
+    assert_eq!(
+        JsxBlock::parse("Hello title=\"hello />"),
+        ErrorContext::fail("\"hello />", "unterminated string in attribute value"),
+    );
+
+    assert_eq!(
+        JsxBlock::parse("Hello title= />"),
+        ErrorContext::fail("/>", "expected attribute value"),
+    );
+
+    assert_eq!(
+        JsxBlock::parse("Hello !name />"),
+        ErrorContext::fail("!name />", "expected attribute, `/>` or `>` in <Hello> tag"),
+    );
+
+    assert_eq!(
+        JsxBlock::parse("Hello name"),
+        ErrorContext::fail("", "unterminated <Hello> tag"),
+    );
+
+    assert_eq!(
+        JsxBlock::parse("Hello when=a > b />"),
+        ErrorContext::fail(
+            "> b />",
+            "bare attribute values cannot contain `>`, use `when={ a > b }` in <Hello> tag"
+        ),
+    );
+
+    assert_eq!(
+        JsxBlock::parse("Hello when=a>b />"),
+        ErrorContext::fail(
+            ">b />",
+            "bare attribute values cannot contain `>`, use `when={ a>b }` in <Hello> tag"
+        ),
+    );
+
+    assert!(JsxBlock::parse("Card title=x>children</Card>").is_ok());
+    assert!(JsxBlock::parse("Link href=url>and/or</Link>").is_ok());
+    assert!(JsxBlock::parse("Card size=sm>1/2 done</Card>").is_ok());
+
+    assert!(Ast::from_str("<Hello />\n<Hello name=\"world />", &Syntax::default())
+        .unwrap_err()
+        .to_string()
+        .starts_with("unterminated string in attribute value\nproblems parsing JSX source at row 2, column 12"));
+}
+
+#[test]
+fn test_jsx_close() {
+    assert_eq!(
+        JsxClose::parse("/Hello>"),
+        Ok(("", JsxClose { name: "Hello" }))
+    );
+
+    assert_eq!(
+        JsxClose::parse("/Hello name>"),
+        ErrorContext::fail("name>", "expected `>` in </Hello> tag"),
+    );
+}
+
+#[test]
+fn test_macro_def() {
+    assert_eq!(
+        MacroDef::parse("{#def name #}", &Syntax::default()),
+        Ok((
+            "",
+            MacroDef {
+                start: "{#def",
+                args: vec![MacroArg {
+                    name: "name",
+                    ty: None,
+                    default: None
+                }]
+            }
+        ))
+    );
+
+    assert_eq!(
+        MacroDef::parse("{#def user_id, name2 title #}", &Syntax::default()),
+        Ok((
+            "",
+            MacroDef {
+                start: "{#def",
+                args: vec![
+                    MacroArg {
+                        name: "user_id",
+                        ty: None,
+                        default: None
+                    },
+                    MacroArg {
+                        name: "name2",
+                        ty: None,
+                        default: None
+                    },
+                    MacroArg {
+                        name: "title",
+                        ty: None,
+                        default: None
+                    },
+                ]
+            }
+        ))
+    );
+
+    assert_eq!(
+        MacroDef::parse("{#def\n  name,\n  title,\n#}", &Syntax::default()),
+        Ok((
+            "",
+            MacroDef {
+                start: "{#def",
+                args: vec![
+                    MacroArg {
+                        name: "name",
+                        ty: None,
+                        default: None
+                    },
+                    MacroArg {
+                        name: "title",
+                        ty: None,
+                        default: None
+                    },
+                ]
+            }
+        ))
+    );
+
+    assert_eq!(
+        MacroDef::parse(
+            "{#def name, greeting = \"Hello\", count={ 1 + 1 } max=10 #}",
+            &Syntax::default()
+        ),
+        Ok((
+            "",
+            MacroDef {
+                start: "{#def",
+                args: vec![
+                    MacroArg {
+                        name: "name",
+                        ty: None,
+                        default: None
+                    },
+                    MacroArg {
+                        name: "greeting",
+                        ty: None,
+                        default: Some("\"Hello\"")
+                    },
+                    MacroArg {
+                        name: "count",
+                        ty: None,
+                        default: Some("1 + 1")
+                    },
+                    MacroArg {
+                        name: "max",
+                        ty: None,
+                        default: Some("10")
+                    },
+                ]
+            }
+        ))
+    );
+
+    assert_eq!(
+        MacroDef::parse(
+            "{#def name: &str, items: Vec<(u32, &'a str)>,\n  count: usize = 1 #}",
+            &Syntax::default()
+        ),
+        Ok((
+            "",
+            MacroDef {
+                start: "{#def",
+                args: vec![
+                    MacroArg {
+                        name: "name",
+                        ty: Some("&str"),
+                        default: None
+                    },
+                    MacroArg {
+                        name: "items",
+                        ty: Some("Vec<(u32, &'a str)>"),
+                        default: None
+                    },
+                    MacroArg {
+                        name: "count",
+                        ty: Some("usize"),
+                        default: Some("1")
+                    },
+                ]
+            }
+        ))
+    );
+
+    assert_eq!(
+        MacroDef::parse("{#def name: #}", &Syntax::default()),
+        ErrorContext::fail("#}", "expected type in {#def #}"),
+    );
+
+    assert_eq!(
+        MacroDef::parse("{#def name = #}", &Syntax::default()),
+        ErrorContext::fail("#}", "expected default value in {#def #}"),
+    );
+
+    assert_eq!(
+        MacroDef::parse("{#def #}", &Syntax::default()),
+        Ok((
+            "",
+            MacroDef {
+                start: "{#def",
+                args: vec![]
+            }
+        ))
+    );
+
+    assert_eq!(
+        MacroDef::parse("{#def name, 2nd #}", &Syntax::default()),
+        ErrorContext::fail("2nd #}", "expected identifier, `,` or `#}` in {#def #}"),
+    );
+
+    assert_eq!(
+        Node::many("{#default #}", &Syntax::default()),
+        Ok((
+            "",
+            vec![Node::Lit(Lit {
+                val: "{#default #}"
+            })]
+        ))
+    );
+}
+
+#[test]
+fn test_lit() {
+    assert_eq!(
+        Lit::parse("Test", &Syntax::default()),
+        Ok(("", Lit { val: "Test" }))
+    );
+
+    for source in [
+        "{% raw %}<Hello name />{% endraw %}",
+        "{%- raw +%}<Hello>{%~ endraw -%}",
+        "{# <Todo> #}",
+        "{# {# <Nested> #} <Todo> #}",
+        "{{ \"<B>\" }}",
+        "{{ \"}}<B>\" }}",
+        "{% let tag = \"<B>\" %}",
+        "{{ '\"' }}",
+        "{{ '\\'' }}{{ \"<B>\" }}",
+        "{% let name: &'a str = \"<B>\" %}",
+        "<script>let users: Array<User> = [];</script>",
+        "<style type=\"text/css\">a::after { content: \"<A>\" }</style>",
+        "<textarea><Hello /></textarea>",
+        "<script>if (a <Foo) {}</SCRIPT>",
+        "<sTyle>a::after { content: \"<A>\" }</style>",
+        "{{ \"<B>",
+    ] {
+        assert_eq!(
+            Lit::parse(source, &Syntax::default()),
+            Ok(("", Lit { val: source }))
+        );
+    }
+
+    assert_eq!(
+        Lit::parse("{{ '\"' }}<Hello />", &Syntax::default()),
+        Ok(("<Hello />", Lit { val: "{{ '\"' }}" }))
+    );
+    assert_eq!(
+        Lit::parse("{# <Todo> #}<Hello />", &Syntax::default()),
+        Ok((
+            "<Hello />",
+            Lit {
+                val: "{# <Todo> #}"
+            }
+        ))
+    );
+    assert_eq!(
+        Lit::parse(
+            "<script>if (a <Foo) {}</SCRIPT><Hello />",
+            &Syntax::default()
+        ),
+        Ok((
+            "<Hello />",
+            Lit {
+                val: "<script>if (a <Foo) {}</SCRIPT>"
+            }
+        ))
+    );
+    assert_eq!(
+        Lit::parse("<scripts><Hello />", &Syntax::default()),
+        Ok(("<Hello />", Lit { val: "<scripts>" }))
+    );
+}
+
+#[test]
+fn test_node() {
+    assert_eq!(Node::many("", &Syntax::default()), Ok(("", vec![])));
+
+    // Components named like raw text elements are not HTML.
+    let (_, nodes) = Node::many("<Textarea label=\"x\" /><Hello />", &Syntax::default()).unwrap();
+    assert!(matches!(
+        &nodes[..],
+        [Node::JsxBlock(textarea), Node::JsxBlock(hello)]
+            if textarea.name == "Textarea" && hello.name == "Hello"
+    ));
+
+    assert_eq!(
+        Node::many("<Hello />", &Syntax::default()),
+        Ok((
+            "",
+            vec![Node::JsxBlock(JsxBlock {
+                name: "Hello",
+                args: vec![],
+                spread: None,
+                self_closing: true,
+            })]
+        ))
+    );
+
+    assert_eq!(
+        Node::many("<Hello />\nTest", &Syntax::default()),
+        Ok((
+            "",
+            vec![
+                Node::JsxBlock(JsxBlock {
+                    name: "Hello",
+                    args: vec![],
+                    spread: None,
+                    self_closing: true,
+                }),
+                Node::Lit(Lit { val: "\nTest" })
+            ]
+        ))
+    );
+
+    assert_eq!(
+        Node::many("Test\n<Hello />", &Syntax::default()),
+        Ok((
+            "",
+            vec![
+                Node::Lit(Lit { val: "Test\n" }),
+                Node::JsxBlock(JsxBlock {
+                    name: "Hello",
+                    args: vec![],
+                    spread: None,
+                    self_closing: true,
+                })
+            ],
+        ))
+    );
+
+    assert_eq!(
+        Node::many("</Hello>", &Syntax::default()),
+        Ok(("", vec![Node::JsxClose(JsxClose { name: "Hello" })]))
+    );
+
+    assert_eq!(
+        Node::many("</Hello>\nTest", &Syntax::default()),
+        Ok((
+            "",
+            vec![
                 Node::JsxClose(JsxClose { name: "Hello" }),
                 Node::Lit(Lit { val: "\nTest" })
             ]
@@ -321,7 +1586,7 @@ fn test_node() {
     );
 
     assert_eq!(
-        Node::many("Test\n</Hello>"),
+        Node::many("Test\n</Hello>", &Syntax::default()),
         Ok((
             "",
             vec![
@@ -331,42 +1596,232 @@ fn test_node() {
         ))
     );
 
-    assert_eq!(Node::many("<"), Ok(("", vec![Node::Lit(Lit { val: "<" })])));
+    assert_eq!(
+        Node::many("<", &Syntax::default()),
+        Ok(("", vec![Node::Lit(Lit { val: "<" })]))
+    );
 
     assert_eq!(
-        Node::many("<i"),
+        Node::many("<i", &Syntax::default()),
         Ok(("", vec![Node::Lit(Lit { val: "<i" })]))
     );
 
     assert_eq!(
-        Node::many("<i>"),
+        Node::many("<i>", &Syntax::default()),
         Ok(("", vec![Node::Lit(Lit { val: "<i>" })]))
     );
 
     assert_eq!(
-        Node::many("<i />"),
+        Node::many("<i />", &Syntax::default()),
         Ok(("", vec![Node::Lit(Lit { val: "<i />" })]))
     );
 
-    assert_eq!(Node::many(">"), Ok(("", vec![Node::Lit(Lit { val: ">" })])));
+    assert_eq!(
+        Node::many(">", &Syntax::default()),
+        Ok(("", vec![Node::Lit(Lit { val: ">" })]))
+    );
 
     assert_eq!(
-        Node::many("/>"),
+        Node::many("/>", &Syntax::default()),
         Ok(("", vec![Node::Lit(Lit { val: "/>" })]))
     );
 
     assert_eq!(
-        Node::many("</"),
+        Node::many("</", &Syntax::default()),
         Ok(("", vec![Node::Lit(Lit { val: "</" })]))
     );
 
     assert_eq!(
-        Node::many("</i"),
+        Node::many("</i", &Syntax::default()),
         Ok(("", vec![Node::Lit(Lit { val: "</i" })]))
     );
 
     assert_eq!(
-        Node::many("</i>"),
+        Node::many("</i>", &Syntax::default()),
         Ok(("", vec![Node::Lit(Lit { val: "</i>" })]))
     );
 }
+
+#[test]
+fn test_component_def() {
+    assert_eq!(
+        ComponentDef::parse(
+            "{#component Badge(text, tone: &str = \"info\") #}",
+            &Syntax::default()
+        ),
+        Ok((
+            "",
+            ComponentDef {
+                start: "{#component",
+                name: "Badge",
+                args: vec![
+                    MacroArg {
+                        name: "text",
+                        ty: None,
+                        default: None
+                    },
+                    MacroArg {
+                        name: "tone",
+                        ty: Some("&str"),
+                        default: Some("\"info\"")
+                    },
+                ]
+            }
+        ))
+    );
+
+    assert_eq!(
+        ComponentDef::parse("{#component Pill(size=10) #}", &Syntax::default()),
+        Ok((
+            "",
+            ComponentDef {
+                start: "{#component",
+                name: "Pill",
+                args: vec![MacroArg {
+                    name: "size",
+                    ty: None,
+                    default: Some("10")
+                }]
+            }
+        ))
+    );
+
+    assert_eq!(
+        ComponentDef::parse("{#component Divider #}", &Syntax::default()),
+        Ok((
+            "",
+            ComponentDef {
+                start: "{#component",
+                name: "Divider",
+                args: vec![]
+            }
+        ))
+    );
+
+    assert_eq!(
+        ComponentDef::parse("{#component badge #}", &Syntax::default()),
+        ErrorContext::fail("badge #}", "expected component name in {#component #}"),
+    );
+
+    assert_eq!(
+        ComponentDef::parse("{#component Badge(text #}", &Syntax::default()),
+        ErrorContext::fail(
+            "#}",
+            "expected identifier, `,` or `)` in {#component Badge #}"
+        ),
+    );
+
+    assert_eq!(
+        Node::many("{#component Badge #}<b>{#/component #}", &Syntax::default()),
+        Ok((
+            "",
+            vec![
+                Node::ComponentDef(ComponentDef {
+                    start: "{#component",
+                    name: "Badge",
+                    args: vec![]
+                }),
+                Node::Lit(Lit { val: "<b>" }),
+                Node::ComponentEnd(ComponentEnd {
+                    start: "{#/component"
+                }),
+            ]
+        ))
+    );
+}
+
+#[test]
+fn test_has_inheritance_tags() {
+    let syntax = Syntax::default();
+    assert!(has_inheritance_tags("{% extends \"base.html\" %}", &syntax));
+    assert!(has_inheritance_tags("<p>{%- block content +%}", &syntax));
+    assert!(!has_inheritance_tags(
+        "{% if blocked %}{% endif %}",
+        &syntax
+    ));
+    assert!(!has_inheritance_tags("{# {% block content %} #}", &syntax));
+    assert!(!has_inheritance_tags("{{ \"{% block %}\" }}", &syntax));
+    assert!(!has_inheritance_tags(
+        "{% raw %}{% block content %}{% endraw %}",
+        &syntax
+    ));
+}
+
+#[test]
+fn test_hoisted_tags() {
+    let syntax = Syntax::default();
+
+    assert_eq!(
+        Node::many(
+            "<p>{%- import \"forms.html\" as forms -%}</p>{% import x %}",
+            &syntax
+        ),
+        Ok((
+            "",
+            vec![
+                Node::Lit(Lit { val: "<p>" }),
+                Node::ImportTag(ImportTag {
+                    val: "{%- import \"forms.html\" as forms -%}",
+                    scope: "forms",
+                }),
+                Node::Lit(Lit {
+                    val: "</p>{% import x %}"
+                }),
+            ]
+        ))
+    );
+
+    assert_eq!(
+        Node::many(
+            "{% macro badge(text) %}<Hello />{%+ endmacro badge %}{% macros %}",
+            &syntax
+        ),
+        Ok((
+            "",
+            vec![
+                Node::MacroTag(MacroTag {
+                    val: "{% macro badge(text) %}",
+                    name: "badge",
+                }),
+                Node::JsxBlock(JsxBlock {
+                    name: "Hello",
+                    args: vec![],
+                    spread: None,
+                    self_closing: true,
+                }),
+                Node::MacroEndTag(MacroEndTag {
+                    val: "{%+ endmacro badge %}"
+                }),
+                Node::Lit(Lit {
+                    val: "{% macros %}"
+                }),
+            ]
+        ))
+    );
+
+    assert_eq!(
+        Node::many("<forms.Field label /></forms.Field><forms.field>", &syntax),
+        Ok((
+            "",
+            vec![
+                Node::JsxBlock(JsxBlock {
+                    name: "forms.Field",
+                    args: vec![JsxAttr {
+                        name: "label",
+                        value: None
+                    }],
+                    spread: None,
+                    self_closing: true,
+                }),
+                Node::JsxClose(JsxClose {
+                    name: "forms.Field"
+                }),
+                Node::Lit(Lit {
+                    val: "<forms.field>"
+                }),
+            ]
+        ))
+    );
+    assert_eq!(scoped_name("forms.Field"), Some(("forms", "Field")));
+    assert_eq!(scoped_name("Forms.Field"), None);
+}
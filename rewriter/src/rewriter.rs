@@ -1,118 +1,672 @@
+use crate::parser::comment_tag;
+use crate::parser::has_inheritance_tags;
+use crate::parser::scoped_name;
+use crate::parser::ComponentDef;
+use crate::parser::ImportTag;
 use crate::parser::JsxBlock;
 use crate::parser::JsxClose;
-use crate::parser::MacroDef;
+use crate::parser::MacroArg;
+use crate::parser::MacroEndTag;
+use crate::parser::MacroTag;
 use crate::parser::Node;
-use std::collections::HashSet;
+use crate::parser::COMPONENT_KEYWORD;
+use crate::parser::MACRO_DEF_KEYWORD;
+use crate::position;
+use crate::Error;
+use crate::Naming;
+use crate::Options;
+use crate::SourceMap;
+use parser::Syntax;
+use std::collections::HashMap;
 use std::path::Path;
 
-pub(crate) struct CompileError;
-
 pub(crate) struct Rewriter<'a> {
+    source: &'a str,
     nodes: &'a [Node<'a>],
+    syntax: &'a Syntax<'a>,
+    naming: Naming,
+    resolve: &'a dyn Fn(&str) -> Result<Option<String>, String>,
+    include: bool,
+    extension: &'a str,
 }
 
 impl<'a> Rewriter<'a> {
-    pub(crate) fn new(nodes: &'a [Node<'a>]) -> Self {
-        Self { nodes }
+    pub(crate) fn new(source: &'a str, nodes: &'a [Node<'a>], options: &'a Options<'a>) -> Self {
+        Self {
+            source,
+            nodes,
+            syntax: options.syntax,
+            naming: options.naming,
+            resolve: options.resolve,
+            include: options.include,
+            extension: options.extension,
+        }
     }
 
-    pub(crate) fn build(&self, macro_name: &str) -> Result<String, CompileError> {
+    pub(crate) fn build(&self, macro_name: &str) -> Result<(String, SourceMap), Error> {
         let mut buf = Buffer::new();
 
         self.rewrite_template(&mut buf, macro_name)?;
 
-        Ok(buf.buf)
+        Ok((buf.buf, buf.map))
     }
 
-    fn rewrite_template(&self, buf: &mut Buffer, macro_name: &str) -> Result<(), CompileError> {
+    fn rewrite_template(&self, buf: &mut Buffer, macro_name: &str) -> Result<(), Error> {
+        let (nodes, hoisted) = self.split_hoisted()?;
+
+        // Imports written in the template must stay at the top level, like the generated ones.
+        for import in &hoisted.imports {
+            buf.mark(self.offset(import.val), true);
+            buf.write(import.val);
+        }
+
         // Collect imports at the top level. https://github.com/djc/askama/issues/931
-        self.write_imports(
+        let imports = self.write_imports(
             buf,
             &self
                 .nodes
                 .iter()
                 .filter_map(|node| match node {
-                    Node::JsxBlock(node) => Some(node),
+                    Node::JsxBlock(node) if !node.is_fragment() && node.slot().is_none() => {
+                        Some(node)
+                    }
                     _ => None,
                 })
                 .collect::<Vec<_>>(),
+            &hoisted,
         )?;
 
+        let (content, components) = self.split_components(&nodes, macro_name)?;
+
+        for local in &hoisted.macros {
+            if local.start.name == macro_name
+                || components
+                    .iter()
+                    .any(|component| snake_case(component.name) == local.start.name)
+            {
+                return Err(self.error(
+                    format!(
+                        "macro `{}` at {} has the name of a component of the template",
+                        local.start.name,
+                        self.position(local.start.val),
+                    ),
+                    local.start.val,
+                ));
+            }
+
+            buf.mark(self.offset(local.start.val), true);
+            buf.write(local.start.val);
+            self.visit_nodes(buf, &local.nodes, &imports)?;
+            buf.mark(self.offset(local.end.val), true);
+            buf.write(local.end.val);
+        }
+
+        let mut defs = content.iter().filter_map(|node| match node {
+            Node::MacroDef(node) => Some(node),
+            _ => None,
+        });
+        let def = defs.next();
+        if let Some(other) = defs.next() {
+            return Err(self.error(
+                format!(
+                    "multiple {} blocks found, the second one at {}",
+                    comment_tag(self.syntax, MACRO_DEF_KEYWORD),
+                    self.position(other.start)
+                ),
+                other.start,
+            ));
+        }
+
+        // Every `{#component}` block gets its own macro, next to the default one.
+        for component in &components {
+            let name = snake_case(component.name);
+            self.write_macro(buf, &name, component.args, self.offset(component.start))?;
+            self.visit_nodes(buf, &component.nodes, &imports)?;
+            self.write_macro_end(buf, &name, self.offset(component.end))?;
+        }
+
+        // Templates using inheritance are rendered as is, `extends` and `block` tags are
+        // only allowed at the top level. So are included ones, in the context of the template
+        // including them.
+        if self.include || self.is_inheritance() {
+            if let Some(def) = def {
+                let reason = if self.include {
+                    "an included template, its props are the variables of the template \
+                     including it"
+                } else {
+                    "a template with `extends` or `block` tags, its props are the fields of the \
+                     template struct"
+                };
+                return Err(self.error(
+                    format!(
+                        "{} at {} cannot be used in {reason}",
+                        comment_tag(self.syntax, MACRO_DEF_KEYWORD),
+                        self.position(def.start)
+                    ),
+                    def.start,
+                ));
+            }
+
+            return self.visit_nodes(buf, &content, &imports);
+        }
+
         // Wrap template in a macro definition.
+        let args = def.map_or(&[][..], |def| &def.args);
         self.write_macro(
             buf,
             macro_name,
-            self.nodes.iter().find_map(|node| match node {
-                Node::MacroDef(node) => Some(node),
-                _ => None,
-            }),
+            args,
+            def.map_or(0, |def| self.offset(def.start)),
         )?;
 
-        self.visit_nodes(buf, self.nodes)?;
+        self.visit_nodes(buf, &content, &imports)?;
 
-        self.write_macro_end(buf, macro_name)?;
+        self.write_macro_end(buf, macro_name, self.source.len())?;
 
         Ok(())
     }
 
-    fn visit_nodes(&self, buf: &mut Buffer, nodes: &[Node<'a>]) -> Result<(), CompileError> {
-        for node in nodes {
+    fn is_inheritance(&self) -> bool {
+        self.nodes.iter().any(|node| match node {
+            Node::Lit(lit) => has_inheritance_tags(lit.val, self.syntax),
+            _ => false,
+        })
+    }
+
+    /// Separates the imports and macros written in the template, which must be at the top
+    /// level, from the rest of its nodes.
+    fn split_hoisted(&self) -> Result<(Vec<&'a Node<'a>>, Hoisted<'a>), Error> {
+        let mut nodes = Vec::new();
+        let mut hoisted = Hoisted::default();
+        let mut open: Option<(&'a MacroTag<'a>, Vec<&'a Node<'a>>)> = None;
+
+        for node in self.nodes {
+            match (node, &mut open) {
+                (Node::ImportTag(import), _) => hoisted.imports.push(import),
+                (Node::MacroTag(start), None) => open = Some((start, Vec::new())),
+                (Node::MacroTag(start), Some((parent, _))) => {
+                    return Err(self.error(
+                        format!(
+                            "macro `{}` at {} cannot be defined inside macro `{}`",
+                            start.name,
+                            self.position(start.val),
+                            parent.name,
+                        ),
+                        start.val,
+                    ));
+                }
+                (Node::MacroEndTag(end), None) => {
+                    return Err(self.error(
+                        format!("unexpected end of macro at {}", self.position(end.val)),
+                        end.val,
+                    ));
+                }
+                (Node::MacroEndTag(end), Some(_)) => {
+                    let (start, nodes) = open.take().unwrap();
+                    hoisted.macros.push(LocalMacro { start, end, nodes });
+                }
+                (Node::ComponentDef(def), Some((parent, _))) => {
+                    return Err(self.error(
+                        format!(
+                            "component `{}` at {} cannot be defined inside macro `{}`",
+                            def.name,
+                            self.position(def.start),
+                            parent.name,
+                        ),
+                        def.start,
+                    ));
+                }
+                (Node::MacroDef(def), Some((parent, _))) => {
+                    return Err(self.error(
+                        format!(
+                            "{} at {} cannot be used inside macro `{}`",
+                            comment_tag(self.syntax, MACRO_DEF_KEYWORD),
+                            self.position(def.start),
+                            parent.name,
+                        ),
+                        def.start,
+                    ));
+                }
+                (node, Some((_, nodes))) => nodes.push(node),
+                (node, None) => nodes.push(node),
+            }
+        }
+
+        if let Some((start, _)) = open {
+            return Err(self.error(
+                format!(
+                    "unclosed macro `{}` at {}",
+                    start.name,
+                    self.position(start.val)
+                ),
+                start.val,
+            ));
+        }
+
+        Ok((nodes, hoisted))
+    }
+
+    /// Separates the `{#component}` blocks of the template from the content of its
+    /// default component.
+    fn split_components(
+        &self,
+        nodes: &[&'a Node<'a>],
+        macro_name: &str,
+    ) -> Result<(Vec<&'a Node<'a>>, Vec<Component<'a>>), Error> {
+        let mut content = Vec::new();
+        let mut components: Vec<Component<'a>> = Vec::new();
+        let mut open: Option<(&'a ComponentDef<'a>, Vec<&'a Node<'a>>)> = None;
+
+        for &node in nodes {
+            match (node, &mut open) {
+                (Node::ComponentDef(def), None) => {
+                    let name = snake_case(def.name);
+                    if name == macro_name {
+                        return Err(self.error(
+                            format!(
+                                "component `{}` at {} has the name of the default component \
+                                 of the template",
+                                def.name,
+                                self.position(def.start),
+                            ),
+                            def.start,
+                        ));
+                    }
+                    if let Some(other) = components
+                        .iter()
+                        .find(|other| snake_case(other.name) == name)
+                    {
+                        return Err(self.error(
+                            format!(
+                                "component `{}` at {} is already defined at {}",
+                                def.name,
+                                self.position(def.start),
+                                self.position(other.start),
+                            ),
+                            def.start,
+                        ));
+                    }
+                    open = Some((def, Vec::new()));
+                }
+                (Node::ComponentDef(def), Some((parent, _))) => {
+                    return Err(self.error(
+                        format!(
+                            "component `{}` at {} cannot be defined inside component `{}`",
+                            def.name,
+                            self.position(def.start),
+                            parent.name,
+                        ),
+                        def.start,
+                    ));
+                }
+                (Node::ComponentEnd(end), None) => {
+                    return Err(self.error(
+                        format!(
+                            "unexpected end of component at {}",
+                            self.position(end.start)
+                        ),
+                        end.start,
+                    ));
+                }
+                (Node::ComponentEnd(end), Some(_)) => {
+                    let (def, nodes) = open.take().unwrap();
+                    components.push(Component {
+                        start: def.start,
+                        end: end.start,
+                        name: def.name,
+                        args: &def.args,
+                        nodes,
+                    });
+                }
+                (Node::MacroDef(def), Some((parent, _))) => {
+                    return Err(self.error(
+                        format!(
+                            "{} at {} cannot be used inside component `{}`, \
+                             declare its props in the {} tag",
+                            comment_tag(self.syntax, MACRO_DEF_KEYWORD),
+                            self.position(def.start),
+                            parent.name,
+                            comment_tag(self.syntax, COMPONENT_KEYWORD),
+                        ),
+                        def.start,
+                    ));
+                }
+                (node, Some((_, nodes))) => nodes.push(node),
+                (node, None) => content.push(node),
+            }
+        }
+
+        if let Some((def, _)) = open {
+            return Err(self.error(
+                format!(
+                    "unclosed component `{}` at {}",
+                    def.name,
+                    self.position(def.start)
+                ),
+                def.start,
+            ));
+        }
+
+        Ok((content, components))
+    }
+
+    fn visit_nodes(
+        &self,
+        buf: &mut Buffer,
+        nodes: &[&'a Node<'a>],
+        imports: &HashMap<&'a str, Import>,
+    ) -> Result<(), Error> {
+        let mut open: Vec<&JsxBlock<'a>> = Vec::new();
+
+        for &node in nodes {
             match node {
                 Node::JsxBlock(node) => {
-                    self.write_call(buf, node)?;
+                    buf.mark(self.offset(node.name), false);
+
+                    if let Some((component, slot)) = node.slot() {
+                        match open.last() {
+                            Some(parent) if parent.name == component => {}
+                            _ => {
+                                return Err(self.error(
+                                    format!(
+                                        "slot <{}> at {} must be a direct child of <{component}>",
+                                        node.name,
+                                        self.position(self.tag_start(node.name)),
+                                    ),
+                                    self.tag_start(node.name),
+                                ));
+                            }
+                        }
+
+                        if !node.args.is_empty() || node.spread.is_some() {
+                            return Err(self.error(
+                                format!(
+                                    "slot <{}> at {} cannot have attributes",
+                                    node.name,
+                                    self.position(self.tag_start(node.name)),
+                                ),
+                                self.tag_start(node.name),
+                            ));
+                        }
+
+                        self.write_slot(buf, slot, node.self_closing)?;
+                    } else if !node.is_fragment() {
+                        // Fragments only group their children, they have no markup of their own.
+                        self.write_call(buf, node, &imports[node.name])?;
+                    }
+
+                    if !node.self_closing {
+                        open.push(node);
+                    }
                 }
                 Node::JsxClose(node) => {
-                    self.write_call_end(buf, node)?;
+                    buf.mark(self.offset(node.name), false);
+
+                    match open.pop() {
+                        Some(block) if block.name == node.name => {}
+                        Some(block) => {
+                            return Err(self.error(
+                                format!(
+                                    "mismatched closing tag </{}> at {}, expected </{}> \
+                                     to close <{}> opened at {}",
+                                    node.name,
+                                    self.position(self.tag_start(node.name)),
+                                    block.name,
+                                    block.name,
+                                    self.position(self.tag_start(block.name)),
+                                ),
+                                self.tag_start(node.name),
+                            ));
+                        }
+                        None => {
+                            return Err(self.error(
+                                format!(
+                                    "unexpected closing tag </{}> at {}",
+                                    node.name,
+                                    self.position(self.tag_start(node.name)),
+                                ),
+                                self.tag_start(node.name),
+                            ));
+                        }
+                    }
+
+                    if node.is_slot() {
+                        self.write_slot_end(buf)?;
+                    } else if !node.is_fragment() {
+                        self.write_call_end(buf, node)?;
+                    }
                 }
                 Node::Lit(source) => {
+                    buf.mark(self.offset(source.val), true);
                     buf.write(source.val);
                 }
                 _ => {}
             }
         }
 
+        if let Some(block) = open.pop() {
+            return Err(self.error(
+                format!(
+                    "unclosed tag <{}> at {}",
+                    block.name,
+                    self.position(self.tag_start(block.name)),
+                ),
+                self.tag_start(block.name),
+            ));
+        }
+
         Ok(())
     }
 
-    fn write_imports(&self, buf: &mut Buffer, tags: &[&JsxBlock<'a>]) -> Result<(), CompileError> {
-        let mut imports = HashSet::new();
+    /// Locates a slice of the parsed source, as `row R, column C`.
+    fn position(&self, s: &str) -> String {
+        let (row, column) = position(self.source, self.offset(s));
+        format!("row {row}, column {column}")
+    }
+
+    fn offset(&self, s: &str) -> usize {
+        s.as_ptr() as usize - self.source.as_ptr() as usize
+    }
+
+    /// Returns the source from the `<` of the tag named `name`, where its errors are reported.
+    fn tag_start(&self, name: &str) -> &'a str {
+        let offset = self.offset(name);
+        &self.source[self.source[..offset].rfind('<').unwrap_or(offset)..]
+    }
+
+    fn error(&self, message: String, at: &str) -> Error {
+        Error::new(message, self.source, self.offset(at))
+    }
+
+    /// Writes an import for every component used in the template, and returns how each
+    /// tag is called.
+    fn write_imports(
+        &self,
+        buf: &mut Buffer,
+        tags: &[&JsxBlock<'a>],
+        hoisted: &Hoisted<'a>,
+    ) -> Result<HashMap<&'a str, Import>, Error> {
+        let mut imports: HashMap<&'a str, Import> = HashMap::new();
+        let mut scopes: HashMap<String, &'a str> = HashMap::new();
 
         for tag in tags {
-            let macro_name = normalize(tag.name);
-            let macro_path = format!("{macro_name}.html");
+            if imports.contains_key(tag.name) {
+                continue;
+            }
+
+            let import = self.resolve_import(tag, hoisted)?;
+            let (Some(path), Some(scope_name)) = (&import.path, &import.scope_name) else {
+                imports.insert(tag.name, import);
+                continue;
+            };
 
-            if imports.insert(macro_name.clone()) {
-                buf.writeln(&format!(
-                    "{{%- import \"{macro_path}\" as {macro_name}_scope -%}}",
-                ))?;
+            match scopes.get(scope_name) {
+                Some(other) if imports[other].path == import.path => {}
+                Some(other) => {
+                    return Err(self.error(
+                        format!(
+                            "<{}> at {} and <{other}> at {} both import as scope `{scope_name}`",
+                            tag.name,
+                            self.position(self.tag_start(tag.name)),
+                            self.position(self.tag_start(other)),
+                        ),
+                        self.tag_start(tag.name),
+                    ));
+                }
+                None => {
+                    buf.mark(self.offset(tag.name), false);
+                    buf.writeln(&format!(
+                        "{}- import {path:?} as {scope_name} -{}",
+                        self.syntax.block_start, self.syntax.block_end
+                    ))?;
+                    scopes.insert(scope_name.clone(), tag.name);
+                }
             }
+
+            imports.insert(tag.name, import);
         }
 
-        Ok(())
+        Ok(imports)
+    }
+
+    /// Finds the template of a component. `<forms.Field />` calls a macro of a scope
+    /// imported in the template and `<LocalBadge />` a macro defined in it, if any, as long
+    /// as no component file has its name.
+    /// Otherwise `<Badges.Badge />` is looked up in `badges/badge.html` first, then as the
+    /// `{#component Badge}` block of `badges.html`.
+    fn resolve_import(&self, tag: &JsxBlock<'a>, hoisted: &Hoisted<'a>) -> Result<Import, Error> {
+        if let Some((scope, name)) = scoped_name(tag.name) {
+            if !hoisted.imports.iter().any(|import| import.scope == scope) {
+                return Err(self.error(
+                    format!(
+                        "scope `{scope}` of <{}> at {} is not imported in the template",
+                        tag.name,
+                        self.position(self.tag_start(tag.name)),
+                    ),
+                    self.tag_start(tag.name),
+                ));
+            }
+            return Ok(Import::local(Some(scope), name));
+        }
+
+        let resolve = |name| {
+            (self.resolve)(&component_path(name, self.naming)).map_err(|err| {
+                self.error(
+                    format!(
+                        "unable to resolve <{}> at {}: {err}",
+                        tag.name,
+                        self.position(self.tag_start(tag.name)),
+                    ),
+                    self.tag_start(tag.name),
+                )
+            })
+        };
+
+        if let Some(local) = hoisted
+            .macros
+            .iter()
+            .find(|local| local.start.name == snake_case(tag.name))
+        {
+            // A component file of the same name would be silently shadowed.
+            if let Some(path) = resolve(tag.name)? {
+                return Err(self.error(
+                    format!(
+                        "<{}> at {} calls macro `{}` defined at {}, which has the name of \
+                         component file {path:?}",
+                        tag.name,
+                        self.position(self.tag_start(tag.name)),
+                        local.start.name,
+                        self.position(local.start.val),
+                    ),
+                    self.tag_start(tag.name),
+                ));
+            }
+            return Ok(Import::local(None, tag.name));
+        }
+
+        if let Some(path) = resolve(tag.name)? {
+            return Ok(Import::new(path, tag.name, None));
+        }
+
+        if let Some((file, component)) = tag.name.rsplit_once('.') {
+            if let Some(path) = resolve(file)? {
+                return Ok(Import::new(path, file, Some(component)));
+            }
+        }
+
+        let path = format!(
+            "{}.{}",
+            component_path(tag.name, self.naming),
+            self.extension
+        );
+        Ok(Import::new(path, tag.name, None))
     }
 
     fn write_macro(
         &self,
         buf: &mut Buffer,
         macro_name: &str,
-        def: Option<&MacroDef<'a>>,
-    ) -> Result<(), CompileError> {
-        let macro_args = def.map(|m| m.args.join(", ")).unwrap_or_default();
+        args: &[MacroArg<'a>],
+        offset: usize,
+    ) -> Result<(), Error> {
+        let macro_args = args
+            .iter()
+            .map(|arg| {
+                let mut macro_arg = arg.name.to_owned();
+                if let Some(ty) = arg.ty {
+                    macro_arg.push_str(&format!(": {ty}"));
+                }
+                if let Some(default) = arg.default {
+                    macro_arg.push_str(&format!(" = {default}"));
+                }
+                macro_arg
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
 
-        buf.writeln(&format!("{{% macro {macro_name}({macro_args}) %}}"))
+        buf.mark(offset, false);
+        buf.writeln(&self.block(&format!("macro {macro_name}({macro_args})")))
     }
 
-    fn write_macro_end(&self, buf: &mut Buffer, macro_name: &str) -> Result<(), CompileError> {
-        buf.writeln(&format!("{{% endmacro {macro_name} %}}"))
+    fn write_macro_end(
+        &self,
+        buf: &mut Buffer,
+        macro_name: &str,
+        offset: usize,
+    ) -> Result<(), Error> {
+        buf.mark(offset, false);
+        buf.writeln(&self.block(&format!("endmacro {macro_name}")))
     }
 
-    fn write_call(&self, buf: &mut Buffer, tag: &JsxBlock<'a>) -> Result<(), CompileError> {
-        let macro_name = normalize(tag.name);
-        let macro_args = tag.args.join(", ");
+    fn write_call(
+        &self,
+        buf: &mut Buffer,
+        tag: &JsxBlock<'a>,
+        import: &Import,
+    ) -> Result<(), Error> {
+        let Import {
+            scope_name,
+            macro_name,
+            ..
+        } = import;
+        let macro_args = tag
+            .spread
+            .map(|spread| format!("...{spread}"))
+            .into_iter()
+            .chain(tag.args.iter().map(|arg| match (arg.value, tag.spread) {
+                (Some(value), _) => format!("{}={value}", arg.name),
+                // Next to a spread, which fills the remaining parameters, pass by name.
+                (None, Some(_)) => format!("{0}={0}", arg.name),
+                (None, None) => arg.name.to_string(),
+            }))
+            .collect::<Vec<_>>()
+            .join(", ");
 
-        buf.write(&format!(
-            "{{% call {macro_name}_scope::{macro_name}({macro_args}) %}}"
-        ));
+        let target = match scope_name {
+            Some(scope_name) => format!("{scope_name}::{macro_name}"),
+            None => macro_name.clone(),
+        };
+        buf.write(&self.block(&format!("call {target}({macro_args})")));
 
         if tag.self_closing {
             self.write_call_end(buf, &JsxClose { name: tag.name })?;
@@ -121,22 +675,106 @@ impl<'a> Rewriter<'a> {
         Ok(())
     }
 
-    fn write_call_end(&self, buf: &mut Buffer, _tag: &JsxClose<'a>) -> Result<(), CompileError> {
-        buf.write("{% endcall %}");
+    fn write_call_end(&self, buf: &mut Buffer, _tag: &JsxClose<'a>) -> Result<(), Error> {
+        buf.write(&self.block("endcall"));
+        Ok(())
+    }
+
+    fn write_slot(&self, buf: &mut Buffer, slot: &str, self_closing: bool) -> Result<(), Error> {
+        buf.write(&self.block(&format!("slot {slot}")));
+
+        if self_closing {
+            self.write_slot_end(buf)?;
+        }
+
         Ok(())
     }
+
+    fn write_slot_end(&self, buf: &mut Buffer) -> Result<(), Error> {
+        buf.write(&self.block("endslot"));
+        Ok(())
+    }
+
+    /// Formats a block tag in the configured syntax, like `{% endcall %}`.
+    fn block(&self, content: &str) -> String {
+        format!(
+            "{} {content} {}",
+            self.syntax.block_start, self.syntax.block_end
+        )
+    }
+}
+
+/// A `{#component}` block of the template.
+struct Component<'a> {
+    start: &'a str,
+    end: &'a str,
+    name: &'a str,
+    args: &'a [MacroArg<'a>],
+    nodes: Vec<&'a Node<'a>>,
+}
+
+/// The imports and `{% macro %}` blocks written in the template, moved to its top level.
+#[derive(Default)]
+struct Hoisted<'a> {
+    imports: Vec<&'a ImportTag<'a>>,
+    macros: Vec<LocalMacro<'a>>,
+}
+
+/// A `{% macro %}` block of the template, whose content may use components.
+struct LocalMacro<'a> {
+    start: &'a MacroTag<'a>,
+    end: &'a MacroEndTag<'a>,
+    nodes: Vec<&'a Node<'a>>,
+}
+
+/// How a component tag is called: the imported template, its scope and the macro.
+/// Macros of the template itself, or of a scope it imports, need no import.
+struct Import {
+    path: Option<String>,
+    scope_name: Option<String>,
+    macro_name: String,
+}
+
+impl Import {
+    /// `file` is the component name the template was found for, `component` the name of
+    /// a `{#component}` block in it, if the tag does not call its default component.
+    fn new(path: String, file: &str, component: Option<&str>) -> Self {
+        let file_path = component_path(file, Naming::SnakeCase);
+        Self {
+            path: Some(path),
+            scope_name: Some(scope_name(&file_path)),
+            macro_name: component.map_or_else(|| normalize(&file_path), snake_case),
+        }
+    }
+
+    fn local(scope: Option<&str>, name: &str) -> Self {
+        Self {
+            path: None,
+            scope_name: scope.map(str::to_owned),
+            macro_name: snake_case(name),
+        }
+    }
 }
 
 pub(crate) struct Buffer {
     pub(crate) buf: String,
+    pub(crate) map: SourceMap,
 }
 
 impl Buffer {
     pub(crate) fn new() -> Self {
-        Self { buf: String::new() }
+        Self {
+            buf: String::new(),
+            map: SourceMap::default(),
+        }
+    }
+
+    /// Maps what is written next to `offset` in the original source.
+    pub(crate) fn mark(&mut self, offset: usize, verbatim: bool) {
+        self.map.push(self.buf.len(), offset, verbatim);
     }
 
-    pub(crate) fn writeln(&mut self, s: &str) -> Result<(), CompileError> {
+    pub(crate) fn writeln(&mut self, s: &str) -> Result<(), Error> {
         if !s.is_empty() {
             self.write(s);
         }
@@ -149,15 +787,333 @@ impl Buffer {
     }
 }
 
-pub(crate) fn normalize<P>(path: P) -> String
+/// Resolves a component name to its template path following `naming`, without extension:
+/// `<Forms.TextInput />` lives in `forms/text_input.html` by default.
+fn component_path(name: &str, naming: Naming) -> String {
+    name.split('.')
+        .map(|segment| match naming {
+            Naming::SnakeCase => snake_case(segment),
+            Naming::KebabCase => snake_case(segment).replace('_', "-"),
+            Naming::PascalCase => segment.to_owned(),
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Every template path gets its own import scope, e.g. `forms_text_input_scope`.
+fn scope_name(path: &str) -> String {
+    format!("{}_scope", path.replace('/', "_"))
+}
+
+fn snake_case(s: &str) -> String {
+    let chars = s.chars().collect::<Vec<_>>();
+    let mut buf = String::with_capacity(s.len());
+
+    for (i, c) in chars.iter().enumerate() {
+        let is_boundary = i > 0
+            && c.is_uppercase()
+            && (!chars[i - 1].is_uppercase()
+                || chars.get(i + 1).is_some_and(|next| next.is_lowercase()));
+
+        if is_boundary {
+            buf.push('_');
+        }
+        buf.extend(c.to_lowercase());
+    }
+
+    buf
+}
+
+/// Returns the name of the macro defined by the template at `path`, from its file stem:
+/// `hello_world.html`, `hello-world.html` and `HelloWorld.html` all define `hello_world`.
+pub fn normalize<P>(path: P) -> String
 where
     P: AsRef<Path>,
 {
-    path.as_ref()
-        .file_stem()
-        .unwrap()
-        .to_str()
-        .unwrap()
-        .to_lowercase()
-        .replace(['-', '.'], "_")
+    snake_case(path.as_ref().file_stem().unwrap().to_str().unwrap()).replace(['-', '.'], "_")
+}
+
+#[test]
+fn test_component_path() {
+    let snake = |name| component_path(name, Naming::SnakeCase);
+    assert_eq!(snake("Hello"), "hello");
+    assert_eq!(snake("Card2"), "card2");
+    assert_eq!(snake("HelloWorld"), "hello_world");
+    assert_eq!(snake("HTMLInput"), "html_input");
+    assert_eq!(snake("Forms.TextInput"), "forms/text_input");
+    assert_eq!(
+        component_path("Forms.TextInput", Naming::KebabCase),
+        "forms/text-input"
+    );
+    assert_eq!(
+        component_path("Forms.TextInput", Naming::PascalCase),
+        "Forms/TextInput"
+    );
+    assert_eq!(normalize("hello_world.html"), "hello_world");
+    assert_eq!(normalize("hello-world.html"), "hello_world");
+    assert_eq!(normalize("HelloWorld.html"), "hello_world");
+    assert_eq!(scope_name("admin/button"), "admin_button_scope");
+    assert_eq!(scope_name("site/button"), "site_button_scope");
+}
+
+#[test]
+fn test_fragment() {
+    assert_eq!(
+        build("<Card><><Child />Test</></Card>").unwrap(),
+        "\
+        {%- import \"card.html\" as card_scope -%}\n\
+        {%- import \"child.html\" as child_scope -%}\n\
+        {% macro index() %}\n\
+        {% call card_scope::card() %}\
+        {% call child_scope::child() %}{% endcall %}Test\
+        {% endcall %}{% endmacro index %}\n"
+    );
+}
+
+#[test]
+fn test_spread() {
+    assert_eq!(
+        build("<Hello {...user} name title=\"Hi\" />").unwrap(),
+        "\
+        {%- import \"hello.html\" as hello_scope -%}\n\
+        {% macro index() %}\n\
+        {% call hello_scope::hello(...user, name=name, title=\"Hi\") %}{% endcall %}\
+        {% endmacro index %}\n"
+    );
+}
+
+#[test]
+fn test_slot() {
+    assert_eq!(
+        build("<Card><Card:header>Title</Card:header>Body<Card:footer /></Card>").unwrap(),
+        "\
+        {%- import \"card.html\" as card_scope -%}\n\
+        {% macro index() %}\n\
+        {% call card_scope::card() %}\
+        {% slot header %}Title{% endslot %}Body{% slot footer %}{% endslot %}\
+        {% endcall %}{% endmacro index %}\n"
+    );
+
+    assert_eq!(
+        build("<Card><Child><Card:header /></Child></Card>")
+            .unwrap_err()
+            .to_string(),
+        "slot <Card:header> at row 1, column 13 must be a direct child of <Card>"
+    );
+
+    assert_eq!(
+        build("<Card><Card:header title /></Card>")
+            .unwrap_err()
+            .to_string(),
+        "slot <Card:header> at row 1, column 6 cannot have attributes"
+    );
+}
+
+#[test]
+fn test_macro_def() {
+    assert_eq!(
+        build("{#def name, user_id #}{{ name }}").unwrap(),
+        "{% macro index(name, user_id) %}\n{{ name }}{% endmacro index %}\n"
+    );
+
+    assert_eq!(
+        build("{#def name, greeting = \"Hello\" #}{{ greeting }}").unwrap(),
+        "{% macro index(name, greeting = \"Hello\") %}\n{{ greeting }}{% endmacro index %}\n"
+    );
+
+    assert_eq!(
+        build("{#def name: &str, count: usize = 1 #}").unwrap(),
+        "{% macro index(name: &str, count: usize = 1) %}\n{% endmacro index %}\n"
+    );
+
+    assert_eq!(
+        build("{#def name #}{#def title #}")
+            .unwrap_err()
+            .to_string(),
+        "multiple {#def #} blocks found, the second one at row 1, column 13"
+    );
+}
+
+#[cfg(test)]
+fn build(source: &str) -> Result<String, Error> {
+    let syntax = Syntax::default();
+    let options = Options::new(&syntax);
+    let ast = crate::parser::Ast::from_str(source, &syntax).unwrap();
+    Rewriter::new(source, &ast.nodes, &options)
+        .build("index")
+        .map(|(source, _)| source)
+}
+
+#[test]
+fn test_unbalanced_tags() {
+    assert!(build("<Card><Child></Child></Card>").is_ok());
+
+    assert_eq!(
+        build("<Card>\n  <Child>\n</Card></Child>")
+            .unwrap_err()
+            .to_string(),
+        "mismatched closing tag </Card> at row 3, column 0, \
+         expected </Child> to close <Child> opened at row 2, column 2"
+    );
+
+    assert_eq!(
+        build("<Card />\n</Card>").unwrap_err().to_string(),
+        "unexpected closing tag </Card> at row 2, column 0"
+    );
+
+    assert_eq!(
+        build("<Card>\n  <Child>\n</Card>").unwrap_err().to_string(),
+        "mismatched closing tag </Card> at row 3, column 0, \
+         expected </Child> to close <Child> opened at row 2, column 2"
+    );
+
+    assert_eq!(
+        build("<Card>\n  <Child />").unwrap_err().to_string(),
+        "unclosed tag <Card> at row 1, column 0"
+    );
+
+    assert_eq!(
+        build("<Card><></Card></>").unwrap_err().to_string(),
+        "mismatched closing tag </Card> at row 1, column 8, \
+         expected </> to close <> opened at row 1, column 6"
+    );
+
+    assert_eq!(
+        build("<>\n</>\n</>").unwrap_err().to_string(),
+        "unexpected closing tag </> at row 3, column 0"
+    );
+}
+
+#[test]
+fn test_component_blocks() {
+    assert_eq!(
+        build(
+            "{#def title #}\
+             {#component Badge(text, tone = \"info\") #}<b>{{ text }}</b>{#/component #}\
+             {#component Divider #}<hr>{#/component #}\
+             <h1>{{ title }}</h1>"
+        )
+        .unwrap(),
+        "\
+        {% macro badge(text, tone = \"info\") %}\n<b>{{ text }}</b>{% endmacro badge %}\n\
+        {% macro divider() %}\n<hr>{% endmacro divider %}\n\
+        {% macro index(title) %}\n<h1>{{ title }}</h1>{% endmacro index %}\n"
+    );
+
+    assert_eq!(
+        build("{#component Badge #}{#component Pill #}{#/component #}{#/component #}")
+            .unwrap_err()
+            .to_string(),
+        "component `Pill` at row 1, column 20 cannot be defined inside component `Badge`"
+    );
+
+    assert_eq!(
+        build("{#component Badge #}\n{#def text #}{#/component #}")
+            .unwrap_err()
+            .to_string(),
+        "{#def #} at row 2, column 0 cannot be used inside component `Badge`, \
+         declare its props in the {#component #} tag"
+    );
+
+    assert_eq!(
+        build("{#component Badge #}{#/component #}\n{#component Badge #}{#/component #}")
+            .unwrap_err()
+            .to_string(),
+        "component `Badge` at row 2, column 0 is already defined at row 1, column 0"
+    );
+
+    assert_eq!(
+        build("{#component Index #}{#/component #}")
+            .unwrap_err()
+            .to_string(),
+        "component `Index` at row 1, column 0 has the name of the default component \
+         of the template"
+    );
+
+    assert_eq!(
+        build("{#component Badge #}<b>").unwrap_err().to_string(),
+        "unclosed component `Badge` at row 1, column 0"
+    );
+
+    assert_eq!(
+        build("<b>{#/component #}").unwrap_err().to_string(),
+        "unexpected end of component at row 1, column 3"
+    );
+}
+
+#[test]
+fn test_inheritance() {
+    assert_eq!(
+        build("{% extends \"base.html\" %}{% block content %}<Card title />{% endblock %}")
+            .unwrap(),
+        "\
+        {%- import \"card.html\" as card_scope -%}\n\
+        {% extends \"base.html\" %}{% block content %}\
+        {% call card_scope::card(title) %}{% endcall %}{% endblock %}"
+    );
+
+    assert_eq!(
+        build("<Nav />{% block content %}{% endblock %}").unwrap(),
+        "\
+        {%- import \"nav.html\" as nav_scope -%}\n\
+        {% call nav_scope::nav() %}{% endcall %}{% block content %}{% endblock %}"
+    );
+
+    assert_eq!(
+        build("{#def title #}{% extends \"base.html\" %}")
+            .unwrap_err()
+            .to_string(),
+        "{#def #} at row 1, column 0 cannot be used in a template with `extends` or `block` \
+         tags, its props are the fields of the template struct"
+    );
+}
+
+#[test]
+fn test_hoisted() {
+    assert_eq!(
+        build(
+            "{% import \"forms.html\" as forms %}\
+             {% macro local_badge(text) %}<Card>{{ text }}</Card>{% endmacro %}\
+             <forms.Field label=\"Name\" /><LocalBadge text=\"new\" />"
+        )
+        .unwrap(),
+        "\
+        {% import \"forms.html\" as forms %}\
+        {%- import \"card.html\" as card_scope -%}\n\
+        {% macro local_badge(text) %}{% call card_scope::card() %}{{ text }}{% endcall %}\
+        {% endmacro %}\
+        {% macro index() %}\n\
+        {% call forms::field(label=\"Name\") %}{% endcall %}\
+        {% call local_badge(text=\"new\") %}{% endcall %}\
+        {% endmacro index %}\n"
+    );
+
+    assert_eq!(
+        build("<forms.Field />").unwrap_err().to_string(),
+        "scope `forms` of <forms.Field> at row 1, column 0 is not imported in the template"
+    );
+
+    assert_eq!(
+        build("{% macro badge() %}{% macro pill() %}")
+            .unwrap_err()
+            .to_string(),
+        "macro `pill` at row 1, column 19 cannot be defined inside macro `badge`"
+    );
+
+    assert_eq!(
+        build("{% macro badge() %}").unwrap_err().to_string(),
+        "unclosed macro `badge` at row 1, column 0"
+    );
+
+    assert_eq!(
+        build("{% endmacro %}").unwrap_err().to_string(),
+        "unexpected end of macro at row 1, column 0"
+    );
+
+    assert_eq!(
+        build("{% macro index() %}{% endmacro %}")
+            .unwrap_err()
+            .to_string(),
+        "macro `index` at row 1, column 0 has the name of a component of the template"
+    );
 }
@@ -0,0 +1,59 @@
+/// Maps offsets in a rewritten template back to the JSX source it was generated from.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SourceMap {
+    segments: Vec<Segment>,
+}
+
+/// A run of rewritten source, starting at `rewritten`. Verbatim segments are copied as is
+/// from `original`, the others are generated from the JSX tag found at `original`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Segment {
+    rewritten: usize,
+    original: usize,
+    verbatim: bool,
+}
+
+impl SourceMap {
+    pub(crate) fn push(&mut self, rewritten: usize, original: usize, verbatim: bool) {
+        // A segment that did not produce any output is replaced by the next one.
+        if matches!(self.segments.last(), Some(last) if last.rewritten == rewritten) {
+            self.segments.pop();
+        }
+
+        self.segments.push(Segment {
+            rewritten,
+            original,
+            verbatim,
+        });
+    }
+
+    /// Returns the offset in the original source of `offset` in the rewritten source.
+    /// Offsets inside generated code map to the tag it was generated from.
+    pub fn original_offset(&self, offset: usize) -> usize {
+        let index = self.segments.partition_point(|s| s.rewritten <= offset);
+
+        match index.checked_sub(1).map(|index| self.segments[index]) {
+            Some(s) if s.verbatim => s.original + (offset - s.rewritten),
+            Some(s) => s.original,
+            None => 0,
+        }
+    }
+}
+
+#[test]
+fn test_original_offset() {
+    let mut map = SourceMap::default();
+    map.push(0, 0, false);
+    map.push(10, 0, true);
+    map.push(15, 5, false);
+    map.push(15, 6, false);
+    map.push(30, 12, true);
+
+    assert_eq!(map.original_offset(0), 0);
+    assert_eq!(map.original_offset(9), 0);
+    assert_eq!(map.original_offset(10), 0);
+    assert_eq!(map.original_offset(14), 4);
+    assert_eq!(map.original_offset(15), 6);
+    assert_eq!(map.original_offset(29), 6);
+    assert_eq!(map.original_offset(31), 13);
+}